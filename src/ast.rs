@@ -0,0 +1,280 @@
+use num_bigint::BigInt;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Negate,
+    /// Bitwise complement, `~`.
+    Not,
+}
+
+impl UnaryOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            UnaryOp::Negate => "-",
+            UnaryOp::Not => "~",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+}
+
+impl BinaryOp {
+    pub fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Add | BinaryOp::Subtract => 5,
+            BinaryOp::Multiply | BinaryOp::Divide => 6,
+            BinaryOp::Power => 7,
+        }
+    }
+
+    pub fn is_right_associative(self) -> bool {
+        self == BinaryOp::Power
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Power => "^",
+        }
+    }
+}
+
+/// Integer-only operators, binding looser than arithmetic as in C: `1 << 2 + 1` is `1 << 3`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitOp {
+    And,
+    Or,
+    Xor,
+    ShiftLeft,
+    ShiftRight,
+}
+
+impl BitOp {
+    pub fn precedence(self) -> u8 {
+        match self {
+            BitOp::Or => 1,
+            BitOp::Xor => 2,
+            BitOp::And => 3,
+            BitOp::ShiftLeft | BitOp::ShiftRight => 4,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BitOp::And => "&",
+            BitOp::Or => "|",
+            BitOp::Xor => "xor",
+            BitOp::ShiftLeft => "<<",
+            BitOp::ShiftRight => ">>",
+        }
+    }
+}
+
+/// Binding power of unary minus: looser than `^` so `-2^2` is `-(2^2)`.
+pub const UNARY_PRECEDENCE: u8 = 7;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// A decimal literal, kept as its source digits so that `precision` and `mode frac` see
+    /// every digit rather than the nearest `f64`.
+    Number(String),
+    /// An exact integer literal such as `0xFF`.
+    Integer(BigInt),
+    Ident(String),
+    /// A name written after a number, as in `9.81 m/s^2`. It reads like `Ident` but may also
+    /// name a unit; elsewhere only the target of `to` reads names as units.
+    Unit(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Bitwise(BitOp, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    /// A list literal, `[1, 2, 3]`.
+    List(Vec<Expr>),
+    /// `value to unit`, re-expressing a quantity in another unit.
+    Convert(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Names this expression reads as variables, in order of first appearance. The target of
+    /// a conversion is a unit, not a variable, and is skipped.
+    pub fn identifiers(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        self.collect_identifiers(&mut names);
+        names
+    }
+
+    fn collect_identifiers(&self, names: &mut Vec<String>) {
+        match self {
+            Expr::Number(_) | Expr::Integer(_) => {}
+            Expr::Ident(name) | Expr::Unit(name) => {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+            Expr::Unary(_, operand) | Expr::Convert(operand, _) => {
+                operand.collect_identifiers(names)
+            }
+            Expr::Binary(_, lhs, rhs) | Expr::Bitwise(_, lhs, rhs) => {
+                lhs.collect_identifiers(names);
+                rhs.collect_identifiers(names);
+            }
+            Expr::Call(_, items) | Expr::List(items) => {
+                for item in items {
+                    item.collect_identifiers(names);
+                }
+            }
+        }
+    }
+
+    /// Replaces the named variables with expressions, as when inlining a user function's body.
+    /// The target of a conversion is a unit and is left alone.
+    pub fn substitute(&self, bindings: &HashMap<String, Expr>) -> Expr {
+        let substitute = |expr: &Expr| Box::new(expr.substitute(bindings));
+        match self {
+            Expr::Ident(name) | Expr::Unit(name) => {
+                bindings.get(name).cloned().unwrap_or_else(|| self.clone())
+            }
+            Expr::Number(_) | Expr::Integer(_) => self.clone(),
+            Expr::Unary(op, operand) => Expr::Unary(*op, substitute(operand)),
+            Expr::Binary(op, lhs, rhs) => Expr::Binary(*op, substitute(lhs), substitute(rhs)),
+            Expr::Bitwise(op, lhs, rhs) => Expr::Bitwise(*op, substitute(lhs), substitute(rhs)),
+            Expr::Call(name, args) => Expr::Call(
+                name.clone(),
+                args.iter().map(|arg| arg.substitute(bindings)).collect(),
+            ),
+            Expr::List(items) => {
+                Expr::List(items.iter().map(|item| item.substitute(bindings)).collect())
+            }
+            Expr::Convert(value, unit) => Expr::Convert(substitute(value), unit.clone()),
+        }
+    }
+
+    /// Precedence of the outermost node, used to decide where `Display` needs parentheses.
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Binary(op, _, _) => op.precedence(),
+            Expr::Bitwise(op, _, _) => op.precedence(),
+            Expr::Unary(..) => UNARY_PRECEDENCE,
+            Expr::Convert(..) => 0,
+            _ => u8::MAX,
+        }
+    }
+
+    /// Whether this is the unit after a number: unit names combined by `*`, `/` and powers.
+    fn is_unit(&self) -> bool {
+        match self {
+            Expr::Unit(_) => true,
+            Expr::Binary(BinaryOp::Multiply | BinaryOp::Divide, lhs, rhs) => {
+                lhs.is_unit() && rhs.is_unit()
+            }
+            Expr::Binary(BinaryOp::Power, base, _) => base.is_unit(),
+            _ => false,
+        }
+    }
+}
+
+/// Writes `lhs op rhs`, parenthesizing operands that bind looser than `op`.
+fn write_infix(
+    f: &mut fmt::Formatter,
+    symbol: &str,
+    precedence: u8,
+    right_assoc: bool,
+    lhs: &Expr,
+    rhs: &Expr,
+) -> fmt::Result {
+    write_operand(
+        f,
+        lhs,
+        lhs.precedence() < precedence || (right_assoc && lhs.precedence() == precedence),
+    )?;
+    write!(f, " {} ", symbol)?;
+    write_operand(
+        f,
+        rhs,
+        rhs.precedence() < precedence || (!right_assoc && rhs.precedence() == precedence),
+    )
+}
+
+fn write_list(f: &mut fmt::Formatter, items: &[Expr]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+fn write_operand(f: &mut fmt::Formatter, expr: &Expr, parenthesize: bool) -> fmt::Result {
+    if parenthesize {
+        write!(f, "({})", expr)
+    } else {
+        write!(f, "{}", expr)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Number(digits) => write!(f, "{}", digits),
+            Expr::Integer(n) => write!(f, "{}", n),
+            Expr::Ident(name) | Expr::Unit(name) => write!(f, "{}", name),
+            Expr::Unary(op, operand) => {
+                write!(f, "{}", op.symbol())?;
+                let nested_unary = matches!(**operand, Expr::Unary(..));
+                write_operand(
+                    f,
+                    operand,
+                    nested_unary || operand.precedence() < UNARY_PRECEDENCE,
+                )
+            }
+            // `9.81 m / s ^ 2` rather than `9.81 * (m / s ^ 2)`, so it reads back as units.
+            Expr::Binary(BinaryOp::Multiply, lhs, rhs)
+                if matches!(**lhs, Expr::Number(_)) && rhs.is_unit() =>
+            {
+                write!(f, "{} {}", lhs, rhs)
+            }
+            Expr::Binary(op, lhs, rhs) => write_infix(
+                f,
+                op.symbol(),
+                op.precedence(),
+                op.is_right_associative(),
+                lhs,
+                rhs,
+            ),
+            Expr::Bitwise(op, lhs, rhs) => {
+                write_infix(f, op.symbol(), op.precedence(), false, lhs, rhs)
+            }
+            Expr::Call(name, args) => {
+                write!(f, "{}(", name)?;
+                write_list(f, args)?;
+                write!(f, ")")
+            }
+            Expr::List(items) => {
+                write!(f, "[")?;
+                write_list(f, items)?;
+                write!(f, "]")
+            }
+            Expr::Convert(value, unit) => write!(f, "{} to {}", value, unit),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Expr(Expr),
+    Assign(String, Expr),
+    Define(String, Vec<String>, Expr),
+}
@@ -0,0 +1,524 @@
+use crate::ast::Expr;
+use crate::complex;
+use crate::constants::{self, CONSTANTS};
+use crate::eval::is_builtin;
+use crate::programmer::{self, Overflow, Word};
+use crate::value::{format_real, Value};
+use colored::*;
+use std::collections::BTreeMap;
+
+pub struct HistoryEntry {
+    pub expression: String,
+    pub result: Value,
+}
+
+/// A reference to an earlier result: `ans`/`_` for the latest, `ansN` for history entry N.
+pub enum HistoryRef {
+    Last,
+    Entry(usize),
+}
+
+impl HistoryRef {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "ans" | "_" => Some(HistoryRef::Last),
+            _ => name
+                .strip_prefix("ans")
+                .filter(|digits| digits.chars().all(|c| c.is_ascii_digit()))
+                .and_then(|digits| digits.parse().ok())
+                .map(HistoryRef::Entry),
+        }
+    }
+}
+
+pub struct UserFunction {
+    pub params: Vec<String>,
+    pub body: Expr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AngleMode {
+    Degrees,
+    Radians,
+    Gradians,
+}
+
+impl AngleMode {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "deg" => Some(AngleMode::Degrees),
+            "rad" => Some(AngleMode::Radians),
+            "grad" => Some(AngleMode::Gradians),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AngleMode::Degrees => "deg",
+            AngleMode::Radians => "rad",
+            AngleMode::Gradians => "grad",
+        }
+    }
+
+    /// Converts an angle in this mode to radians, for feeding trig functions.
+    pub fn to_radians(self, angle: f64) -> f64 {
+        match self {
+            AngleMode::Degrees => angle.to_radians(),
+            AngleMode::Radians => angle,
+            AngleMode::Gradians => angle * std::f64::consts::PI / 200.0,
+        }
+    }
+
+    /// Converts an angle in radians to this mode, for results of inverse trig functions.
+    pub fn convert_radians(self, angle: f64) -> f64 {
+        match self {
+            AngleMode::Degrees => angle.to_degrees(),
+            AngleMode::Radians => angle,
+            AngleMode::Gradians => angle * 200.0 / std::f64::consts::PI,
+        }
+    }
+}
+
+/// Largest digit count accepted by the `precision` command.
+const MAX_PRECISION: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumberMode {
+    Float,
+    /// Arbitrary-precision decimal arithmetic with this many significant digits.
+    Decimal(usize),
+    /// Exact rational arithmetic; irrational results fall back to floating point.
+    Fraction,
+    /// Complex arithmetic with `i` as the imaginary unit.
+    Complex,
+    /// Integer literals, with integer results shown in decimal, hex, octal and binary.
+    Programmer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComplexFormat {
+    Rectangular,
+    Polar,
+}
+
+/// Settings accepted by the `mode` command. Anything else after `mode` is an expression, so
+/// the statistics function `mode [1, 2, 2]` still works.
+pub const MODES: &[&str] = &[
+    "deg", "rad", "grad", "float", "frac", "complex", "prog", "rect", "polar",
+];
+
+impl NumberMode {
+    pub fn label(self) -> Option<String> {
+        match self {
+            NumberMode::Float => None,
+            NumberMode::Decimal(digits) => Some(format!("{} digits", digits)),
+            NumberMode::Fraction => Some("frac".to_string()),
+            NumberMode::Complex => Some("complex".to_string()),
+            NumberMode::Programmer => Some("prog".to_string()),
+        }
+    }
+}
+
+pub struct Calculator {
+    pub angle_mode: AngleMode,
+    pub number_mode: NumberMode,
+    pub complex_format: ComplexFormat,
+    /// Fixed-width integer type for `mode prog`; `None` means unbounded integers.
+    pub word: Option<Word>,
+    pub overflow: Overflow,
+    /// Exact integers longer than this are shortened when printed; `None` prints every digit.
+    pub max_digits: Option<usize>,
+    memory: f64,
+    history: Vec<HistoryEntry>,
+    variables: BTreeMap<String, Value>,
+    functions: BTreeMap<String, UserFunction>,
+}
+
+impl Calculator {
+    pub fn new() -> Self {
+        Self {
+            angle_mode: AngleMode::Degrees,
+            number_mode: NumberMode::Float,
+            complex_format: ComplexFormat::Rectangular,
+            word: None,
+            overflow: Overflow::Wrap,
+            max_digits: None,
+            memory: 0.0,
+            history: Vec::new(),
+            variables: BTreeMap::new(),
+            functions: BTreeMap::new(),
+        }
+    }
+
+    /// Whether `mode <argument>` changes a setting rather than taking the statistical mode. It
+    /// does for the names in `MODES` and for any other bare name without a value, so a mistyped
+    /// `mode degs` lists the valid modes instead of reporting an unknown variable.
+    pub fn is_mode_setting(&self, argument: &str) -> bool {
+        let is_name = argument
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_')
+            && argument.chars().all(|c| c.is_alphanumeric() || c == '_');
+        MODES.contains(&argument.to_lowercase().as_str())
+            || (is_name
+                && self.variable(argument).is_none()
+                && constants::lookup(argument).is_none()
+                && HistoryRef::parse(argument).is_none())
+    }
+
+    pub fn set_mode(&mut self, name: &str) -> Result<(), String> {
+        if let Some(mode) = AngleMode::parse(name) {
+            self.angle_mode = mode;
+            println!(
+                "{}",
+                format!("Angle mode set to {}.", mode.name()).bright_green()
+            );
+            return Ok(());
+        }
+        self.number_mode = match name {
+            "float" => NumberMode::Float,
+            "frac" => NumberMode::Fraction,
+            "complex" => NumberMode::Complex,
+            "prog" => NumberMode::Programmer,
+            "rect" | "polar" => {
+                self.complex_format = if name == "polar" {
+                    ComplexFormat::Polar
+                } else {
+                    ComplexFormat::Rectangular
+                };
+                println!(
+                    "{}",
+                    format!("Complex results will be shown in {} form.", name).bright_green()
+                );
+                return Ok(());
+            }
+            _ => {
+                return Err(format!(
+                "Unknown mode '{}' (expected deg, rad, grad, float, frac, complex, prog, rect or polar)",
+                name
+            ))
+            }
+        };
+        println!("{}", format!("Number mode set to {}.", name).bright_green());
+        Ok(())
+    }
+
+    pub fn set_precision(&mut self, setting: &str) -> Result<(), String> {
+        self.number_mode = match setting {
+            "off" => NumberMode::Float,
+            _ => NumberMode::Decimal(
+                setting
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|digits| (1..=MAX_PRECISION).contains(digits))
+                    .ok_or_else(|| {
+                        format!(
+                            "Expected a digit count from 1 to {} or 'off'",
+                            MAX_PRECISION
+                        )
+                    })?,
+            ),
+        };
+        if let NumberMode::Decimal(digits) = self.number_mode {
+            println!(
+                "{}",
+                format!("Evaluating with {} significant digits.", digits).bright_green()
+            );
+        } else {
+            println!("{}", "Evaluating in floating point.".bright_green());
+        }
+        Ok(())
+    }
+
+    /// Selects a fixed-width integer type such as `i32`, switching to `mode prog`.
+    pub fn set_word(&mut self, setting: &str) -> Result<(), String> {
+        if setting == "off" {
+            self.word = None;
+            println!("{}", "Integers are unbounded.".bright_green());
+            return Ok(());
+        }
+        let word = Word::parse(setting).ok_or_else(|| {
+            format!(
+                "Unknown word type '{}' (expected i8, i16, i32, i64, i128, u8, ..., u128 or off)",
+                setting
+            )
+        })?;
+        self.word = Some(word);
+        self.number_mode = NumberMode::Programmer;
+        println!(
+            "{}",
+            format!(
+                "Using {} integers ({} on overflow).",
+                word.name(),
+                self.overflow.name()
+            )
+            .bright_green()
+        );
+        Ok(())
+    }
+
+    pub fn set_overflow(&mut self, setting: &str) -> Result<(), String> {
+        self.overflow = Overflow::parse(setting).ok_or_else(|| {
+            format!(
+                "Unknown overflow mode '{}' (expected wrap, saturate or error)",
+                setting
+            )
+        })?;
+        println!(
+            "{}",
+            format!("Overflow mode set to {}.", setting).bright_green()
+        );
+        Ok(())
+    }
+
+    /// Text shown inside the prompt brackets, e.g. `deg` or `rad, 50 digits`.
+    pub fn prompt_label(&self) -> String {
+        let label = match (self.number_mode, self.word) {
+            (NumberMode::Programmer, Some(word)) => Some(format!("prog {}", word.name())),
+            (mode, _) => mode.label(),
+        };
+        match label {
+            Some(label) => format!("{}, {}", self.angle_mode.name(), label),
+            None => self.angle_mode.name().to_string(),
+        }
+    }
+
+    pub fn set_max_digits(&mut self, setting: &str) -> Result<(), String> {
+        self.max_digits = match setting {
+            "off" | "all" => None,
+            _ => Some(
+                setting
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&digits| digits > 0)
+                    .ok_or("Expected a positive digit count or 'off'")?,
+            ),
+        };
+        match self.max_digits {
+            Some(digits) => println!(
+                "{}",
+                format!("Integers longer than {} digits will be truncated.", digits).bright_green()
+            ),
+            None => println!("{}", "Integers will be printed in full.".bright_green()),
+        }
+        Ok(())
+    }
+
+    pub fn format_value(&self, value: &Value) -> String {
+        match (value, self.complex_format) {
+            (Value::Complex(z), ComplexFormat::Polar) => complex::format_polar(*z, self.angle_mode),
+            (Value::Integer(n), _) if self.number_mode == NumberMode::Programmer => {
+                programmer::format_bases(n, self.word)
+            }
+            // Grids start on their own line so the rows stay aligned after `name =`.
+            (Value::Matrix(m), _) => m
+                .to_string()
+                .lines()
+                .map(|row| format!("\n  {}", row))
+                .collect(),
+            _ => value.format(self.max_digits),
+        }
+    }
+
+    /// `=` for a result, or `≈` in `frac` mode when it had to fall back to floating point.
+    pub fn relation(&self, value: &Value) -> &'static str {
+        match (self.number_mode, value) {
+            (NumberMode::Fraction, Value::Real(_) | Value::Quantity(_) | Value::Matrix(_)) => "≈",
+            _ => "=",
+        }
+    }
+
+    pub fn store_in_memory(&mut self, value: f64) {
+        self.memory = value;
+        println!("{}", "Value stored in memory.".bright_green());
+    }
+
+    pub fn add_to_memory(&mut self, value: f64) {
+        self.memory += value;
+        println!("{}", "Value added to memory.".bright_green());
+    }
+
+    pub fn recall_memory(&self) -> f64 {
+        self.memory
+    }
+
+    pub fn clear_memory(&mut self) {
+        self.memory = 0.0;
+        println!("{}", "Memory cleared.".bright_green());
+    }
+
+    pub fn add_to_history(&mut self, expression: &str, result: Value) {
+        self.history.push(HistoryEntry {
+            expression: expression.to_string(),
+            result,
+        });
+    }
+
+    pub fn history_result(&self, reference: HistoryRef) -> Result<Value, String> {
+        match reference {
+            HistoryRef::Last => self
+                .history
+                .last()
+                .map(|entry| entry.result.clone())
+                .ok_or_else(|| "No previous result".to_string()),
+            HistoryRef::Entry(index) => index
+                .checked_sub(1)
+                .and_then(|i| self.history.get(i))
+                .map(|entry| entry.result.clone())
+                .ok_or_else(|| format!("No history entry {}", index)),
+        }
+    }
+
+    pub fn show_history(&self) {
+        println!("\n{}", "Calculation History:".bright_blue());
+        if self.history.is_empty() {
+            println!("No calculations yet.");
+        } else {
+            for (i, entry) in self.history.iter().enumerate() {
+                println!(
+                    "{}. {} {} {}",
+                    i + 1,
+                    entry.expression,
+                    self.relation(&entry.result),
+                    self.format_value(&entry.result)
+                );
+            }
+        }
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&Value> {
+        self.variables.get(name)
+    }
+
+    pub fn set_variable(&mut self, name: &str, value: Value) -> Result<(), String> {
+        if is_builtin(name) {
+            return Err(format!("Cannot assign to built-in function '{}'", name));
+        }
+        if constants::lookup(name).is_some() {
+            return Err(format!("Cannot reassign constant '{}'", name));
+        }
+        if HistoryRef::parse(name).is_some() {
+            return Err(format!("'{}' is reserved for previous results", name));
+        }
+        self.variables.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Removes a variable, or a user function if no variable has that name.
+    pub fn unset(&mut self, name: &str) -> Result<(), String> {
+        if self.variables.remove(name).is_some() {
+            println!("{}", format!("Variable '{}' removed.", name).bright_green());
+            Ok(())
+        } else if self.functions.remove(name).is_some() {
+            println!("{}", format!("Function '{}' removed.", name).bright_green());
+            Ok(())
+        } else {
+            Err(format!("Unknown variable or function '{}'", name))
+        }
+    }
+
+    pub fn show_variables(&self) {
+        println!("\n{}", "Variables:".bright_blue());
+        if self.variables.is_empty() {
+            println!("No variables defined.");
+        } else {
+            for (name, value) in &self.variables {
+                println!(
+                    "{} {} {}",
+                    name,
+                    self.relation(value),
+                    self.format_value(value)
+                );
+            }
+        }
+    }
+
+    pub fn show_constants(&self) {
+        println!("\n{}", "Constants:".bright_blue());
+        let width = CONSTANTS.iter().map(|c| c.name.len()).max().unwrap_or(0);
+        for constant in CONSTANTS {
+            let value = match constant.unit {
+                "" => format_real(constant.value),
+                unit => format!("{} {}", format_real(constant.value), unit),
+            };
+            println!(
+                "{:width$} = {:28} {}",
+                constant.name,
+                value,
+                constant.description,
+                width = width
+            );
+        }
+    }
+
+    pub fn function(&self, name: &str) -> Option<&UserFunction> {
+        self.functions.get(name)
+    }
+
+    pub fn define_function(
+        &mut self,
+        name: &str,
+        params: Vec<String>,
+        body: Expr,
+    ) -> Result<(), String> {
+        if is_builtin(name) {
+            return Err(format!("Cannot redefine built-in function '{}'", name));
+        }
+        self.functions
+            .insert(name.to_string(), UserFunction { params, body });
+        Ok(())
+    }
+
+    pub fn show_functions(&self) {
+        println!("\n{}", "Functions:".bright_blue());
+        if self.functions.is_empty() {
+            println!("No functions defined.");
+        } else {
+            for (name, function) in &self.functions {
+                println!(
+                    "{}({}) = {}",
+                    name,
+                    function.params.join(", "),
+                    function.body
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats;
+
+    #[test]
+    fn accepts_every_listed_mode() {
+        let mut calc = Calculator::new();
+        for mode in MODES {
+            assert_eq!(calc.set_mode(mode), Ok(()), "{}", mode);
+        }
+        assert!(calc.set_mode("median").is_err());
+    }
+
+    #[test]
+    fn treats_unknown_names_after_mode_as_settings() {
+        let mut calc = Calculator::new();
+        assert!(calc.is_mode_setting("RAD"));
+        assert!(calc.is_mode_setting("degs"));
+        assert!(calc.set_mode("degs").is_err());
+        calc.set_variable("data", Value::Real(2.0)).unwrap();
+        assert!(!calc.is_mode_setting("data"));
+        assert!(!calc.is_mode_setting("pi"));
+        assert!(!calc.is_mode_setting("ans"));
+        assert!(!calc.is_mode_setting("[1, 2, 2]"));
+        assert!(!calc.is_mode_setting("(1, 2, 2)"));
+    }
+
+    #[test]
+    fn leaves_statistics_functions_out_of_the_mode_command() {
+        for name in stats::FUNCTIONS {
+            assert!(!MODES.contains(name), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use crate::special;
+use crate::value::Value;
+use num_bigint::BigInt;
+use num_traits::{One, ToPrimitive};
+
+/// Largest argument accepted by the exact integer functions; beyond this the results take
+/// too long to compute and print to be useful in an interactive session.
+const MAX_ARGUMENT: u64 = 100_000;
+
+pub const FUNCTIONS: &[&str] = &["fact", "dfact", "ncr", "npr", "multinomial"];
+
+pub fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+    let result = match (name, args) {
+        // Non-integers go through the gamma function: x! = Γ(x + 1).
+        ("fact", [x]) if x.to_integer().is_none() => {
+            return special::gamma(x.to_f64() + 1.0).map(Value::Real)
+        }
+        ("fact", [n]) => factorial(argument(n, "Factorial")?),
+        ("dfact", [n]) => double_factorial(argument(n, "Double factorial")?),
+        ("ncr", [n, r]) => combinations(argument(n, "nCr")?, argument(r, "nCr")?),
+        ("npr", [n, r]) => permutations(argument(n, "nPr")?, argument(r, "nPr")?),
+        ("multinomial", ks) if !ks.is_empty() => {
+            let ks = ks
+                .iter()
+                .map(|k| argument(k, "Multinomial"))
+                .collect::<Result<Vec<_>, _>>()?;
+            multinomial(&ks)?
+        }
+        _ => {
+            return Err(format!(
+                "Wrong number of arguments for '{}' (got {})",
+                name,
+                args.len()
+            ))
+        }
+    };
+    Ok(Value::Integer(result))
+}
+
+fn argument(value: &Value, function: &str) -> Result<u64, String> {
+    let n = value
+        .to_integer()
+        .and_then(|n| n.to_u64())
+        .ok_or_else(|| format!("{} only defined for non-negative integers!", function))?;
+    if n > MAX_ARGUMENT {
+        return Err(format!(
+            "{} argument too large (maximum {})",
+            function, MAX_ARGUMENT
+        ));
+    }
+    Ok(n)
+}
+
+/// Product of `lo..=hi` stepping by `step`, split recursively so the big multiplications
+/// stay balanced.
+fn product(lo: u64, hi: u64, step: u64) -> BigInt {
+    if lo > hi {
+        return BigInt::one();
+    }
+    let count = (hi - lo) / step + 1;
+    if count <= 16 {
+        return (0..count).map(|i| BigInt::from(lo + i * step)).product();
+    }
+    let mid = lo + (count / 2) * step;
+    product(lo, mid - step, step) * product(mid, hi, step)
+}
+
+pub fn factorial(n: u64) -> BigInt {
+    product(1, n, 1)
+}
+
+fn double_factorial(n: u64) -> BigInt {
+    if n < 2 {
+        return BigInt::one();
+    }
+    product(2 - n % 2, n, 2)
+}
+
+fn permutations(n: u64, r: u64) -> BigInt {
+    if r > n {
+        return BigInt::from(0);
+    }
+    product(n - r + 1, n, 1)
+}
+
+fn combinations(n: u64, r: u64) -> BigInt {
+    if r > n {
+        return BigInt::from(0);
+    }
+    let r = r.min(n - r);
+    permutations(n, r) / factorial(r)
+}
+
+fn multinomial(ks: &[u64]) -> Result<BigInt, String> {
+    let mut total = 0;
+    let mut result = BigInt::one();
+    for &k in ks {
+        total += k;
+        if total > MAX_ARGUMENT {
+            return Err(format!(
+                "Multinomial total too large (maximum {})",
+                MAX_ARGUMENT
+            ));
+        }
+        result *= combinations(total, k);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn integer(n: i64) -> Value {
+        Value::Integer(BigInt::from(n))
+    }
+
+    fn exact(name: &str, args: &[i64]) -> Result<String, String> {
+        let args: Vec<Value> = args.iter().map(|&n| integer(n)).collect();
+        call(name, &args).map(|value| value.to_string())
+    }
+
+    #[test]
+    fn computes_factorials_exactly() {
+        assert_eq!(exact("fact", &[0]), Ok("1".to_string()));
+        assert_eq!(
+            exact("fact", &[25]),
+            Ok("15511210043330985984000000".to_string())
+        );
+        let big = factorial(200);
+        assert_eq!(big.to_string().len(), 375);
+        assert!(big.to_string().ends_with(&"0".repeat(49)));
+        assert_eq!(big / factorial(199), BigInt::from(200));
+        let half = call("fact", &[Value::Real(0.5)]).unwrap().to_f64();
+        assert!((half - std::f64::consts::PI.sqrt() / 2.0).abs() < 1e-15);
+        assert!(exact("fact", &[-3]).is_err());
+        assert!(exact("fact", &[100_001]).is_err());
+    }
+
+    #[test]
+    fn computes_double_factorials() {
+        assert_eq!(exact("dfact", &[0]), Ok("1".to_string()));
+        assert_eq!(exact("dfact", &[1]), Ok("1".to_string()));
+        assert_eq!(exact("dfact", &[7]), Ok("105".to_string()));
+        assert_eq!(exact("dfact", &[8]), Ok("384".to_string()));
+        assert!(exact("dfact", &[-1]).is_err());
+    }
+
+    #[test]
+    fn counts_combinations_and_permutations() {
+        assert_eq!(exact("ncr", &[5, 2]), Ok("10".to_string()));
+        assert_eq!(exact("npr", &[5, 2]), Ok("20".to_string()));
+        assert_eq!(exact("ncr", &[5, 0]), Ok("1".to_string()));
+        assert_eq!(exact("ncr", &[5, 5]), Ok("1".to_string()));
+        assert_eq!(exact("ncr", &[5, 7]), Ok("0".to_string()));
+        assert_eq!(exact("npr", &[5, 7]), Ok("0".to_string()));
+        assert_eq!(
+            exact("ncr", &[100, 50]),
+            Ok("100891344545564193334812497256".to_string())
+        );
+        assert!(exact("ncr", &[-5, 2]).is_err());
+        assert!(exact("npr", &[5, -2]).is_err());
+        assert!(call("ncr", &[integer(5), Value::Real(2.5)]).is_err());
+        assert!(exact("ncr", &[5]).is_err());
+    }
+
+    #[test]
+    fn computes_multinomial_coefficients() {
+        assert_eq!(exact("multinomial", &[2, 3, 4]), Ok("1260".to_string()));
+        assert_eq!(exact("multinomial", &[4]), Ok("1".to_string()));
+        assert_eq!(exact("multinomial", &[0, 0]), Ok("1".to_string()));
+        assert!(exact("multinomial", &[]).is_err());
+        assert!(exact("multinomial", &[60_000, 60_000]).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+use crate::ast::BinaryOp;
+use crate::calculator::AngleMode;
+use crate::value::format_real;
+use num_complex::Complex64;
+
+/// Components smaller than this fraction of the magnitude are rounding noise, e.g. the
+/// imaginary part of `exp(i * pi)`.
+const DISPLAY_EPSILON: f64 = 1e-14;
+
+pub const FUNCTIONS: &[&str] = &["re", "im", "arg", "conj"];
+
+pub fn apply_binary(op: BinaryOp, a: Complex64, b: Complex64) -> Result<Complex64, String> {
+    match op {
+        BinaryOp::Add => Ok(a + b),
+        BinaryOp::Subtract => Ok(a - b),
+        BinaryOp::Multiply => Ok(a * b),
+        BinaryOp::Divide => {
+            if b == Complex64::new(0.0, 0.0) {
+                Err("Division by zero!".to_string())
+            } else {
+                Ok(a / b)
+            }
+        }
+        BinaryOp::Power => power(a, b),
+    }
+}
+
+fn power(a: Complex64, b: Complex64) -> Result<Complex64, String> {
+    if a == Complex64::new(0.0, 0.0) {
+        return if b.re > 0.0 {
+            Ok(a)
+        } else if b == Complex64::new(0.0, 0.0) {
+            Ok(Complex64::new(1.0, 0.0))
+        } else {
+            Err("Division by zero!".to_string())
+        };
+    }
+    // Integer powers by repeated multiplication stay exact for Gaussian integers like (1+i)^2.
+    if b.im == 0.0 && b.re.fract() == 0.0 && b.re.abs() <= i32::MAX as f64 {
+        return Ok(a.powi(b.re as i32));
+    }
+    Ok(a.powc(b))
+}
+
+fn reciprocal(function: &str, value: Complex64) -> Result<Complex64, String> {
+    if value.norm() < 1e-12 {
+        Err(format!("{} is undefined at this angle!", function))
+    } else {
+        Ok(value.inv())
+    }
+}
+
+/// Evaluates a built-in on complex arguments. Returns `None` for functions that are only
+/// defined on reals, so the caller can evaluate them in floating point.
+pub fn call(
+    name: &str,
+    args: &[Complex64],
+    angle_mode: AngleMode,
+) -> Option<Result<Complex64, String>> {
+    let radians = |z: Complex64| z.scale(angle_mode.to_radians(1.0));
+    let convert = |z: Complex64| z.scale(angle_mode.convert_radians(1.0));
+
+    let result = match (name, args) {
+        ("sqrt", &[z]) => Ok(z.sqrt()),
+        ("sin", &[z]) => Ok(radians(z).sin()),
+        ("cos", &[z]) => Ok(radians(z).cos()),
+        ("tan", &[z]) => Ok(radians(z).tan()),
+        ("asin", &[z]) => Ok(convert(z.asin())),
+        ("acos", &[z]) => Ok(convert(z.acos())),
+        ("atan", &[z]) => Ok(convert(z.atan())),
+        ("sec", &[z]) => reciprocal("Secant", radians(z).cos()),
+        ("csc", &[z]) => reciprocal("Cosecant", radians(z).sin()),
+        ("cot", &[z]) => reciprocal("Cotangent", radians(z).tan()),
+        ("sinh", &[z]) => Ok(z.sinh()),
+        ("cosh", &[z]) => Ok(z.cosh()),
+        ("tanh", &[z]) => Ok(z.tanh()),
+        ("asinh", &[z]) => Ok(z.asinh()),
+        ("acosh", &[z]) => Ok(z.acosh()),
+        ("atanh", &[z]) => {
+            if z == Complex64::new(1.0, 0.0) || z == Complex64::new(-1.0, 0.0) {
+                Err("Inverse hyperbolic tangent is infinite at ±1!".to_string())
+            } else {
+                Ok(z.atanh())
+            }
+        }
+        ("ln", &[z]) | ("log", &[z]) if z == Complex64::new(0.0, 0.0) => {
+            Err("Cannot calculate logarithm of zero!".to_string())
+        }
+        ("ln", &[z]) => Ok(z.ln()),
+        ("log", &[z]) => Ok(z.log10()),
+        ("abs", &[z]) => Ok(Complex64::new(z.norm(), 0.0)),
+        ("re", &[z]) => Ok(Complex64::new(z.re, 0.0)),
+        ("im", &[z]) => Ok(Complex64::new(z.im, 0.0)),
+        ("arg", &[z]) => {
+            if z == Complex64::new(0.0, 0.0) {
+                Err("Argument of zero is undefined!".to_string())
+            } else {
+                Ok(Complex64::new(angle_mode.convert_radians(z.arg()), 0.0))
+            }
+        }
+        ("conj", &[z]) => Ok(z.conj()),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// Drops components that are rounding noise relative to the magnitude.
+fn clean(z: Complex64) -> Complex64 {
+    let threshold = z.norm() * DISPLAY_EPSILON;
+    Complex64::new(
+        if z.re.abs() < threshold { 0.0 } else { z.re },
+        if z.im.abs() < threshold { 0.0 } else { z.im },
+    )
+}
+
+/// `3 + 4i`, `-2i`, `5`.
+pub fn format_rectangular(z: Complex64) -> String {
+    let z = clean(z);
+    let imaginary = |im: f64| {
+        if im == 1.0 {
+            "i".to_string()
+        } else {
+            format!("{}i", format_real(im))
+        }
+    };
+    let (re, im) = (z.re, z.im);
+    if im == 0.0 {
+        format_real(re)
+    } else if re == 0.0 && im < 0.0 {
+        format!("-{}", imaginary(-im))
+    } else if re == 0.0 {
+        imaginary(im)
+    } else if im < 0.0 {
+        format!("{} - {}", format_real(re), imaginary(-im))
+    } else {
+        format!("{} + {}", format_real(re), imaginary(im))
+    }
+}
+
+/// `5 ∠ 53.13010235415598°`, with the angle in the current angle mode.
+pub fn format_polar(z: Complex64, angle_mode: AngleMode) -> String {
+    let z = clean(z);
+    let norm = format_real(z.norm());
+    let angle = format_real(angle_mode.convert_radians(z.arg()));
+    match angle_mode {
+        AngleMode::Degrees => format!("{} ∠ {}°", norm, angle),
+        AngleMode::Radians => format!("{} ∠ {} rad", norm, angle),
+        AngleMode::Gradians => format!("{} ∠ {} grad", norm, angle),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(name: &str, z: Complex64, angle_mode: AngleMode) -> Result<Complex64, String> {
+        call(name, &[z], angle_mode).expect("a complex built-in")
+    }
+
+    #[test]
+    fn takes_principal_values_on_branch_cuts() {
+        let radians = AngleMode::Radians;
+        let root = evaluate("sqrt", Complex64::new(-4.0, 0.0), radians).unwrap();
+        assert_eq!(format_rectangular(root), "2i");
+        // The sign of a zero imaginary part picks the side of the cut.
+        let root = evaluate("sqrt", Complex64::new(-4.0, -0.0), radians).unwrap();
+        assert_eq!(format_rectangular(root), "-2i");
+        let log = evaluate("ln", Complex64::new(-1.0, 0.0), radians).unwrap();
+        assert_eq!(format_rectangular(log), "3.141592653589793i");
+        assert!(evaluate("ln", Complex64::new(0.0, 0.0), radians).is_err());
+        assert!(evaluate("atanh", Complex64::new(1.0, 0.0), radians).is_err());
+        assert!(call("gamma", &[Complex64::new(1.0, 0.0)], radians).is_none());
+    }
+
+    #[test]
+    fn takes_arguments_and_conjugates() {
+        let z = Complex64::new(-1.0, 0.0);
+        assert_eq!(
+            evaluate("arg", z, AngleMode::Degrees),
+            Ok(Complex64::new(180.0, 0.0))
+        );
+        assert_eq!(
+            evaluate("arg", z, AngleMode::Radians),
+            Ok(Complex64::new(std::f64::consts::PI, 0.0))
+        );
+        assert!(evaluate("arg", Complex64::new(0.0, 0.0), AngleMode::Degrees).is_err());
+        assert_eq!(
+            evaluate("conj", Complex64::new(3.0, 4.0), AngleMode::Degrees),
+            Ok(Complex64::new(3.0, -4.0))
+        );
+        assert_eq!(
+            apply_binary(
+                BinaryOp::Power,
+                Complex64::new(1.0, 1.0),
+                Complex64::new(2.0, 0.0)
+            ),
+            Ok(Complex64::new(0.0, 2.0))
+        );
+    }
+
+    #[test]
+    fn formats_rectangular_and_polar_forms() {
+        assert_eq!(format_rectangular(Complex64::new(3.0, 4.0)), "3 + 4i");
+        assert_eq!(format_rectangular(Complex64::new(1.0, -1.0)), "1 - i");
+        assert_eq!(format_rectangular(Complex64::new(0.0, -2.0)), "-2i");
+        assert_eq!(format_rectangular(Complex64::new(5.0, 0.0)), "5");
+        let exp_i_pi = Complex64::new(0.0, std::f64::consts::PI).exp();
+        assert_eq!(format_rectangular(exp_i_pi), "-1");
+        let z = Complex64::new(3.0, 4.0);
+        assert_eq!(
+            format_polar(z, AngleMode::Degrees),
+            "5 ∠ 53.13010235415598°"
+        );
+        assert_eq!(
+            format_polar(z, AngleMode::Radians),
+            "5 ∠ 0.9272952180016122 rad"
+        );
+        assert_eq!(
+            format_polar(Complex64::new(0.0, -1.0), AngleMode::Gradians),
+            "1 ∠ -100 grad"
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use crate::calculator::NumberMode;
+use crate::decimal::Decimal;
+use crate::units::{dim, Dimension, Quantity, DIMENSIONLESS};
+use crate::value::Value;
+use std::f64::consts::{E, PI, TAU};
+
+pub struct Constant {
+    pub name: &'static str,
+    pub value: f64,
+    pub dimension: Dimension,
+    /// How the unit is written when the constant is printed, e.g. `J·s`.
+    pub unit: &'static str,
+    pub description: &'static str,
+}
+
+const fn constant(
+    name: &'static str,
+    value: f64,
+    dimension: Dimension,
+    unit: &'static str,
+    description: &'static str,
+) -> Constant {
+    Constant {
+        name,
+        value,
+        dimension,
+        unit,
+        description,
+    }
+}
+
+/// Mathematical constants, then physical constants with their CODATA 2018 values.
+pub const CONSTANTS: &[Constant] = &[
+    constant(
+        "pi",
+        PI,
+        DIMENSIONLESS,
+        "",
+        "Ratio of a circle's circumference to its diameter",
+    ),
+    constant("tau", TAU, DIMENSIONLESS, "", "2π, a full turn in radians"),
+    constant("e", E, DIMENSIONLESS, "", "Base of the natural logarithm"),
+    constant(
+        "phi",
+        1.618033988749895,
+        DIMENSIONLESS,
+        "",
+        "Golden ratio (1 + √5) / 2",
+    ),
+    constant(
+        "inf",
+        f64::INFINITY,
+        DIMENSIONLESS,
+        "",
+        "Infinity, e.g. as a bound of integrate",
+    ),
+    constant(
+        "c",
+        299792458.0,
+        dim(1, 0, -1, 0, 0),
+        "m/s",
+        "Speed of light in vacuum",
+    ),
+    constant(
+        "G",
+        6.67430e-11,
+        dim(3, -1, -2, 0, 0),
+        "m^3/(kg·s^2)",
+        "Newtonian constant of gravitation",
+    ),
+    constant(
+        "h",
+        6.62607015e-34,
+        dim(2, 1, -1, 0, 0),
+        "J·s",
+        "Planck constant",
+    ),
+    constant(
+        "hbar",
+        1.054571817e-34,
+        dim(2, 1, -1, 0, 0),
+        "J·s",
+        "Reduced Planck constant h / 2π",
+    ),
+    constant(
+        "k_B",
+        1.380649e-23,
+        dim(2, 1, -2, 0, -1),
+        "J/K",
+        "Boltzmann constant",
+    ),
+    constant(
+        "N_A",
+        6.02214076e23,
+        [0, 0, 0, 0, 0, -1, 0],
+        "1/mol",
+        "Avogadro constant",
+    ),
+    constant(
+        "R",
+        8.314462618,
+        [2, 1, -2, 0, -1, -1, 0],
+        "J/(mol·K)",
+        "Molar gas constant",
+    ),
+    constant(
+        "e_charge",
+        1.602176634e-19,
+        dim(0, 0, 1, 1, 0),
+        "C",
+        "Elementary charge",
+    ),
+    constant(
+        "m_e",
+        9.1093837015e-31,
+        dim(0, 1, 0, 0, 0),
+        "kg",
+        "Electron mass",
+    ),
+    constant(
+        "m_p",
+        1.67262192369e-27,
+        dim(0, 1, 0, 0, 0),
+        "kg",
+        "Proton mass",
+    ),
+    constant(
+        "m_n",
+        1.67492749804e-27,
+        dim(0, 1, 0, 0, 0),
+        "kg",
+        "Neutron mass",
+    ),
+    constant(
+        "epsilon_0",
+        8.8541878128e-12,
+        dim(-3, -1, 4, 2, 0),
+        "F/m",
+        "Vacuum electric permittivity",
+    ),
+    constant(
+        "mu_0",
+        1.25663706212e-6,
+        dim(1, 1, -2, -2, 0),
+        "N/A^2",
+        "Vacuum magnetic permeability",
+    ),
+    constant(
+        "sigma",
+        5.670374419e-8,
+        dim(0, 1, -3, 0, -4),
+        "W/(m^2·K^4)",
+        "Stefan–Boltzmann constant",
+    ),
+    constant(
+        "alpha",
+        7.2973525693e-3,
+        DIMENSIONLESS,
+        "",
+        "Fine-structure constant",
+    ),
+    constant(
+        "a_0",
+        5.29177210903e-11,
+        dim(1, 0, 0, 0, 0),
+        "m",
+        "Bohr radius",
+    ),
+    constant(
+        "g_0",
+        9.80665,
+        dim(1, 0, -2, 0, 0),
+        "m/s^2",
+        "Standard acceleration of gravity",
+    ),
+];
+
+pub fn lookup(name: &str) -> Option<&'static Constant> {
+    CONSTANTS.iter().find(|constant| constant.name == name)
+}
+
+impl Constant {
+    /// The constant as a value in the current number mode. Dimensional constants are
+    /// quantities labelled with their usual unit.
+    pub fn value(&self, mode: NumberMode) -> Result<Value, String> {
+        if self.dimension != DIMENSIONLESS {
+            return Ok(Value::Quantity(Quantity {
+                value: self.value,
+                dimension: self.dimension,
+                unit: Some((self.unit.to_string(), 1.0)),
+            }));
+        }
+        let NumberMode::Decimal(digits) = mode else {
+            return Ok(Value::Real(self.value));
+        };
+        if !self.value.is_finite() {
+            return Ok(Value::Real(self.value));
+        }
+        // The mathematical constants are computed to full precision; the rest are measured.
+        let decimal = match self.name {
+            "pi" => Decimal::pi(digits),
+            "tau" => Decimal::pi(digits).mul(&Decimal::from_i64(2, digits))?,
+            "e" => Decimal::e(digits),
+            "phi" => Decimal::from_i64(5, digits)
+                .sqrt()?
+                .add(&Decimal::from_i64(1, digits))
+                .div(&Decimal::from_i64(2, digits))?,
+            _ => Decimal::from_f64(self.value, digits)?,
+        };
+        Ok(Value::Decimal(decimal))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculator::Calculator;
+
+    #[test]
+    fn lists_every_constant_once() {
+        for (i, constant) in CONSTANTS.iter().enumerate() {
+            assert!(
+                CONSTANTS[..i].iter().all(|c| c.name != constant.name),
+                "{} is listed twice",
+                constant.name
+            );
+            assert_eq!(lookup(constant.name).unwrap().name, constant.name);
+            assert!(!constant.description.is_empty());
+        }
+        assert!(lookup("h_planck").is_none());
+    }
+
+    #[test]
+    fn reads_h_as_the_planck_constant() {
+        let h = lookup("h").unwrap();
+        assert_eq!(h.value, 6.62607015e-34);
+        assert_eq!(h.dimension, dim(2, 1, -1, 0, 0));
+        let hbar = lookup("hbar").unwrap();
+        assert!((hbar.value - h.value / TAU).abs() < 1e-42);
+        let Ok(Value::Quantity(quantity)) = h.value(NumberMode::Float) else {
+            panic!("h should be a quantity");
+        };
+        assert_eq!(quantity.unit, Some(("J·s".to_string(), 1.0)));
+    }
+
+    #[test]
+    fn computes_mathematical_constants_to_the_working_precision() {
+        let pi = lookup("pi")
+            .unwrap()
+            .value(NumberMode::Decimal(40))
+            .unwrap();
+        assert!(pi
+            .to_string()
+            .starts_with("3.14159265358979323846264338327950288419"));
+        let phi = lookup("phi").unwrap().value(NumberMode::Float).unwrap();
+        assert_eq!(phi, Value::Real(1.618033988749895));
+        let inf = lookup("inf")
+            .unwrap()
+            .value(NumberMode::Decimal(30))
+            .unwrap();
+        assert_eq!(inf, Value::Real(f64::INFINITY));
+    }
+
+    #[test]
+    fn refuses_to_reassign_constants() {
+        let mut calc = Calculator::new();
+        for name in ["pi", "e", "h", "c"] {
+            assert_eq!(
+                calc.set_variable(name, Value::Real(1.0)),
+                Err(format!("Cannot reassign constant '{}'", name))
+            );
+        }
+    }
+}
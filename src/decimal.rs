@@ -0,0 +1,1055 @@
+use crate::combinatorics::factorial;
+use num_bigint::{BigInt, Sign};
+use num_rational::BigRational;
+use num_traits::{Signed, ToPrimitive, Zero};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Extra significant digits carried through every operation so that rounding error stays
+/// out of the digits that are printed.
+const GUARD_DIGITS: usize = 10;
+
+/// `exp` refuses arguments beyond this magnitude; the result's decimal exponent would no
+/// longer be meaningful.
+const MAX_EXP_ARGUMENT: f64 = 1e15;
+
+/// Integer powers up to this exponent are computed by repeated squaring; larger ones go
+/// through `exp(b * ln a)`.
+const MAX_EXACT_POWER: u64 = 10_000;
+
+/// `to_integer` only builds exact integers up to this many digits; anything larger is beyond
+/// what factorials, powers or programmer mode could use and would take long to build.
+const MAX_INTEGER_DIGITS: i64 = 100_000;
+
+/// Trig functions refuse angles from 10^this on: reducing them needs pi to that many extra
+/// digits, which takes seconds per thousand.
+const MAX_ANGLE_MAGNITUDE: i64 = 1000;
+
+/// Results whose decimal exponent leaves ±this are out of range. The bound is far inside `i64`,
+/// so adding a few digits' worth to an exponent, as rounding and normalization do, can't
+/// overflow.
+const MAX_EXPONENT: i64 = 1 << 60;
+
+/// An arbitrary-precision decimal floating-point number `mantissa * 10^exponent`.
+///
+/// `digits` is the number of significant digits requested by the user; arithmetic keeps
+/// `digits + GUARD_DIGITS` and `Display` rounds back to `digits`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decimal {
+    mantissa: BigInt,
+    exponent: i64,
+    digits: usize,
+}
+
+fn pow10(n: usize) -> BigInt {
+    let n = u32::try_from(n).expect("powers of ten are bounded by the working precision");
+    BigInt::from(10u32).pow(n)
+}
+
+/// The exponent computed by `exponent`, if it neither overflowed nor left the range decimals
+/// are kept in.
+fn checked_exponent(exponent: Option<i64>) -> Result<i64, String> {
+    exponent
+        .filter(|exponent| exponent.abs() <= MAX_EXPONENT)
+        .ok_or_else(|| "Result out of range".to_string())
+}
+
+fn digit_count(n: &BigInt) -> usize {
+    if n.is_zero() {
+        1
+    } else {
+        n.magnitude().to_string().len()
+    }
+}
+
+/// Splits a decimal literal into the exact integer mantissa and power of ten it denotes.
+fn parse_literal(text: &str) -> Result<(BigInt, i64), String> {
+    let invalid = || format!("Invalid number '{}'", text);
+    let (mantissa_text, exponent_text) = text.split_once(['e', 'E']).unwrap_or((text, "0"));
+    let (integer, fraction) = mantissa_text.split_once('.').unwrap_or((mantissa_text, ""));
+    let mantissa: BigInt = format!("{}{}", integer, fraction)
+        .parse()
+        .map_err(|_| invalid())?;
+    let exponent: i64 = exponent_text.parse().map_err(|_| invalid())?;
+    let exponent = checked_exponent(exponent.checked_sub(fraction.len() as i64))?;
+    Ok((mantissa, exponent))
+}
+
+/// The exact fraction a decimal literal denotes, such as 1/8 for `0.125`, or `None` when its
+/// power of ten has more than `MAX_INTEGER_DIGITS` digits.
+pub fn parse_rational(text: &str) -> Option<BigRational> {
+    let (mantissa, exponent) = parse_literal(text).ok()?;
+    if exponent.unsigned_abs() > MAX_INTEGER_DIGITS as u64 {
+        return None;
+    }
+    let scale = pow10(exponent.unsigned_abs() as usize);
+    Some(if exponent >= 0 {
+        BigRational::from_integer(mantissa * scale)
+    } else {
+        BigRational::new(mantissa, scale)
+    })
+}
+
+/// Rounds `mantissa * 10^exponent` to at most `precision` significant digits, half away from
+/// zero.
+fn round_to(mantissa: BigInt, exponent: i64, precision: usize) -> (BigInt, i64) {
+    let count = digit_count(&mantissa);
+    if count <= precision {
+        return (mantissa, exponent);
+    }
+    let drop = count - precision;
+    let divisor = pow10(drop);
+    let mut quotient = &mantissa / &divisor;
+    let remainder = &mantissa % &divisor;
+    if remainder.abs() * 2 >= divisor {
+        quotient += mantissa.signum();
+    }
+    (quotient, exponent + drop as i64)
+}
+
+impl Decimal {
+    fn new(mantissa: BigInt, exponent: i64, digits: usize) -> Self {
+        let (mut mantissa, mut exponent) = round_to(mantissa, exponent, digits + GUARD_DIGITS);
+        if mantissa.is_zero() {
+            return Decimal {
+                mantissa,
+                exponent: 0,
+                digits,
+            };
+        }
+        let ten = BigInt::from(10u32);
+        while (&mantissa % &ten).is_zero() {
+            mantissa /= &ten;
+            exponent += 1;
+        }
+        Decimal {
+            mantissa,
+            exponent,
+            digits,
+        }
+    }
+
+    pub fn from_integer(n: BigInt, digits: usize) -> Self {
+        Decimal::new(n, 0, digits)
+    }
+
+    pub fn from_i64(n: i64, digits: usize) -> Self {
+        Decimal::from_integer(BigInt::from(n), digits)
+    }
+
+    /// Converts through the shortest decimal representation of `value`, so `0.1` becomes
+    /// exactly one tenth rather than the nearest binary fraction.
+    pub fn from_f64(value: f64, digits: usize) -> Result<Self, String> {
+        if !value.is_finite() {
+            return Err(format!("Cannot represent {} as a decimal", value));
+        }
+        Decimal::parse(&format!("{:e}", value), digits)
+    }
+
+    /// Reads a decimal literal such as `1.5`, `.25` or `6.022e23` digit for digit.
+    pub fn parse(text: &str, digits: usize) -> Result<Self, String> {
+        let (mantissa, exponent) = parse_literal(text)?;
+        Ok(Decimal::new(mantissa, exponent, digits))
+    }
+
+    pub fn digits(&self) -> usize {
+        self.digits
+    }
+
+    /// Rounds to a different requested precision.
+    pub fn with_digits(&self, digits: usize) -> Self {
+        Decimal::new(self.mantissa.clone(), self.exponent, digits)
+    }
+
+    fn precision(&self) -> usize {
+        self.digits + GUARD_DIGITS
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.mantissa.sign() == Sign::Minus
+    }
+
+    /// Decimal exponent of the leading digit, i.e. `floor(log10(|self|))`.
+    fn magnitude(&self) -> i64 {
+        self.exponent + digit_count(&self.mantissa) as i64 - 1
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        format!("{}e{}", self.mantissa, self.exponent)
+            .parse()
+            .unwrap_or(f64::NAN)
+    }
+
+    /// Whether this value is a whole number; trailing zeros are normalized into the exponent,
+    /// so that is the case exactly when the exponent is non-negative.
+    pub fn is_integer(&self) -> bool {
+        self.exponent >= 0
+    }
+
+    /// The exact integer this value holds, if it is one of at most `MAX_INTEGER_DIGITS` digits.
+    pub fn to_integer(&self) -> Option<BigInt> {
+        if self.is_integer() && self.magnitude() < MAX_INTEGER_DIGITS {
+            Some(&self.mantissa * pow10(self.exponent as usize))
+        } else {
+            None
+        }
+    }
+
+    /// Rounds to the nearest integer, half away from zero.
+    fn round_integer(&self) -> BigInt {
+        if self.exponent >= 0 {
+            return &self.mantissa * pow10(self.exponent as usize);
+        }
+        let shift = (-self.exponent) as usize;
+        if shift > digit_count(&self.mantissa) {
+            return BigInt::zero();
+        }
+        round_to(
+            self.mantissa.clone(),
+            self.exponent,
+            digit_count(&self.mantissa) - shift,
+        )
+        .0
+    }
+
+    pub fn neg(&self) -> Self {
+        Decimal {
+            mantissa: -&self.mantissa,
+            exponent: self.exponent,
+            digits: self.digits,
+        }
+    }
+
+    pub fn abs(&self) -> Self {
+        Decimal {
+            mantissa: self.mantissa.abs(),
+            exponent: self.exponent,
+            digits: self.digits,
+        }
+    }
+
+    fn compare(&self, other: &Decimal) -> Ordering {
+        self.sub(other).mantissa.sign().cmp(&Sign::NoSign)
+    }
+
+    fn is_negligible_against(&self, other: &Decimal) -> bool {
+        !other.is_zero()
+            && (self.is_zero()
+                || other.magnitude() - self.magnitude() > other.precision() as i64 + 1)
+    }
+
+    pub fn add(&self, other: &Decimal) -> Self {
+        let digits = self.digits.max(other.digits);
+        if self.is_negligible_against(other) {
+            return other.with_digits(digits);
+        }
+        if other.is_negligible_against(self) {
+            return self.with_digits(digits);
+        }
+        let exponent = self.exponent.min(other.exponent);
+        let a = &self.mantissa * pow10((self.exponent - exponent) as usize);
+        let b = &other.mantissa * pow10((other.exponent - exponent) as usize);
+        Decimal::new(a + b, exponent, digits)
+    }
+
+    pub fn sub(&self, other: &Decimal) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Decimal) -> Result<Self, String> {
+        checked_exponent(self.exponent.checked_add(other.exponent))?;
+        Ok(self.product(other))
+    }
+
+    /// `self * other` without the range check, for series and reductions whose operands are
+    /// known to be small.
+    fn product(&self, other: &Decimal) -> Self {
+        Decimal::new(
+            &self.mantissa * &other.mantissa,
+            self.exponent + other.exponent,
+            self.digits.max(other.digits),
+        )
+    }
+
+    pub fn div(&self, other: &Decimal) -> Result<Self, String> {
+        if other.is_zero() {
+            return Err("Division by zero!".to_string());
+        }
+        let (quotient, exponent) = self.quotient(other);
+        let exponent = checked_exponent(exponent)?;
+        Ok(Decimal::new(
+            quotient,
+            exponent,
+            self.digits.max(other.digits),
+        ))
+    }
+
+    /// The mantissa and exponent of `self / other`, or `None` for the exponent if it overflowed.
+    fn quotient(&self, other: &Decimal) -> (BigInt, Option<i64>) {
+        let digits = self.digits.max(other.digits);
+        let shift = (digits + GUARD_DIGITS + digit_count(&other.mantissa) + 2)
+            .saturating_sub(digit_count(&self.mantissa));
+        let quotient = (&self.mantissa * pow10(shift)) / &other.mantissa;
+        let exponent = self
+            .exponent
+            .checked_sub(other.exponent)
+            .and_then(|exponent| exponent.checked_sub(shift as i64));
+        (quotient, exponent)
+    }
+
+    /// Division by a small non-zero integer, which moves the exponent by no more than the
+    /// working precision.
+    fn div_i64(&self, n: i64) -> Self {
+        let (quotient, exponent) = self.quotient(&Decimal::from_i64(n, self.digits));
+        Decimal::new(
+            quotient,
+            exponent.expect("a small divisor keeps the exponent in range"),
+            self.digits,
+        )
+    }
+
+    fn mul_i64(&self, n: i64) -> Self {
+        self.product(&Decimal::from_i64(n, self.digits))
+    }
+
+    pub fn sqrt(&self) -> Result<Self, String> {
+        if self.is_negative() {
+            return Err("Cannot calculate square root of negative number!".to_string());
+        }
+        if self.is_zero() {
+            return Ok(self.clone());
+        }
+        // Scale so the integer square root has full precision and the exponent stays even.
+        let target = 2 * (self.precision() + 2);
+        let mut shift = target.saturating_sub(digit_count(&self.mantissa)) as i64;
+        if (self.exponent - shift) % 2 != 0 {
+            shift += 1;
+        }
+        let root = (&self.mantissa * pow10(shift as usize)).sqrt();
+        Ok(Decimal::new(root, (self.exponent - shift) / 2, self.digits))
+    }
+
+    pub fn pi(digits: usize) -> Self {
+        // Machin's formula: pi = 16 atan(1/5) - 4 atan(1/239).
+        let work = digits + 5;
+        let a = atan_inverse(5, work).mul_i64(16);
+        let b = atan_inverse(239, work).mul_i64(4);
+        a.sub(&b).with_digits(digits)
+    }
+
+    pub fn e(digits: usize) -> Self {
+        Decimal::from_i64(1, digits + 5)
+            .exp()
+            .expect("exp(1) is in range")
+            .with_digits(digits)
+    }
+
+    fn ln2(digits: usize) -> Self {
+        let third = Decimal::one(digits + 5).div_i64(3);
+        atanh_series(&third).mul_i64(2).with_digits(digits)
+    }
+
+    fn ln10(digits: usize) -> Self {
+        // ln 10 = 3 ln 2 + ln 1.25, and ln 1.25 = 2 atanh(1/9).
+        let work = digits + 5;
+        let ninth = Decimal::one(work).div_i64(9);
+        Decimal::ln2(work)
+            .mul_i64(3)
+            .add(&atanh_series(&ninth).mul_i64(2))
+            .with_digits(digits)
+    }
+
+    fn one(digits: usize) -> Self {
+        Decimal::from_i64(1, digits)
+    }
+
+    pub fn ln(&self) -> Result<Self, String> {
+        if self.is_negative() || self.is_zero() {
+            return Err("Cannot calculate natural logarithm of non-positive number!".to_string());
+        }
+        let power = self.magnitude();
+        let work = self.digits + 5 + digit_count(&BigInt::from(power));
+
+        // Reduce to y in [1, 10), then halve until y <= 1.5 so the atanh series converges fast.
+        let mut y = Decimal {
+            mantissa: self.mantissa.clone(),
+            exponent: self.exponent - power,
+            digits: work,
+        };
+        let limit = Decimal::from_i64(3, work).div_i64(2);
+        let mut halvings = 0;
+        while y.compare(&limit) == Ordering::Greater {
+            y = y.div_i64(2);
+            halvings += 1;
+        }
+
+        let one = Decimal::one(work);
+        let z = y.sub(&one).div(&y.add(&one))?;
+        let result = atanh_series(&z)
+            .mul_i64(2)
+            .add(&Decimal::ln2(work).mul_i64(halvings))
+            .add(&Decimal::ln10(work).mul_i64(power));
+        Ok(result.with_digits(self.digits))
+    }
+
+    pub fn log10(&self) -> Result<Self, String> {
+        if self.is_negative() || self.is_zero() {
+            return Err("Cannot calculate logarithm of non-positive number!".to_string());
+        }
+        let work = self.with_digits(self.digits + 5);
+        Ok(work
+            .ln()?
+            .div(&Decimal::ln10(work.digits))?
+            .with_digits(self.digits))
+    }
+
+    pub fn exp(&self) -> Result<Self, String> {
+        if self.abs().to_f64() > MAX_EXP_ARGUMENT {
+            return Err("Exponent too large!".to_string());
+        }
+        let work = self.digits + 5 + self.magnitude().max(0) as usize;
+        let x = self.with_digits(work);
+
+        // exp(x) = 10^n * exp(r) with r in [0, ln 10), then exp(r) = exp(r / 256)^256.
+        let ln10 = Decimal::ln10(work);
+        let n = x.div(&ln10)?.floor_integer();
+        let r = x.sub(&ln10.mul(&Decimal::from_integer(n.clone(), work))?);
+        let mut result = exp_series(&r.div_i64(256));
+        for _ in 0..8 {
+            result = result.product(&result);
+        }
+
+        let n = n.to_i64().ok_or("Exponent too large!")?;
+        Ok(Decimal {
+            exponent: checked_exponent(result.exponent.checked_add(n))?,
+            ..result
+        }
+        .with_digits(self.digits))
+    }
+
+    fn floor_integer(&self) -> BigInt {
+        let rounded = self.round_integer();
+        if Decimal::from_integer(rounded.clone(), self.digits).compare(self) == Ordering::Greater {
+            rounded - 1
+        } else {
+            rounded
+        }
+    }
+
+    /// Raises to an arbitrary power: exact repeated squaring for integer exponents, otherwise
+    /// `exp(b * ln a)`.
+    pub fn pow(&self, exponent: &Decimal) -> Result<Self, String> {
+        let digits = self.digits.max(exponent.digits);
+        if self.is_zero() {
+            return match exponent.mantissa.sign() {
+                Sign::Plus => Ok(Decimal::from_i64(0, digits)),
+                Sign::NoSign => Ok(Decimal::one(digits)),
+                Sign::Minus => Err("Division by zero!".to_string()),
+            };
+        }
+
+        let integer = exponent.to_integer();
+        if let Some(n) = integer.as_ref().and_then(|n| n.abs().to_u64()) {
+            if n <= MAX_EXACT_POWER {
+                let work = digits + 5 + digit_count(&BigInt::from(n));
+                let mut base = self.with_digits(work);
+                let mut result = Decimal::one(work);
+                let mut remaining = n;
+                while remaining > 0 {
+                    if remaining & 1 == 1 {
+                        result = result.mul(&base)?;
+                    }
+                    remaining >>= 1;
+                    if remaining > 0 {
+                        base = base.mul(&base)?;
+                    }
+                }
+                if exponent.is_negative() {
+                    result = Decimal::one(work).div(&result)?;
+                }
+                return Ok(result.with_digits(digits));
+            }
+        }
+
+        // A positive exponent means a factor of ten, so only exponent 0 can be odd.
+        let odd_integer = exponent.exponent == 0 && (&exponent.mantissa % 2u32) != BigInt::zero();
+        if self.is_negative() && !exponent.is_integer() {
+            return Err("Cannot raise negative number to a non-integer power!".to_string());
+        }
+        let work = digits + 5;
+        let magnitude = exponent
+            .with_digits(work)
+            .mul(&self.abs().with_digits(work).ln()?)?
+            .exp()?;
+        let result = if self.is_negative() && odd_integer {
+            magnitude.neg()
+        } else {
+            magnitude
+        };
+        Ok(result.with_digits(digits))
+    }
+
+    /// Reduces an angle in radians to [-pi, pi] at working precision.
+    fn reduce_angle(&self) -> Result<Decimal, String> {
+        if self.magnitude() >= MAX_ANGLE_MAGNITUDE {
+            return Err(format!(
+                "Angles must be smaller than 1e{} in precision mode",
+                MAX_ANGLE_MAGNITUDE
+            ));
+        }
+        let work = self.digits + 5 + self.magnitude().max(0) as usize;
+        let x = self.with_digits(work);
+        let two_pi = Decimal::pi(work).mul_i64(2);
+        let turns = x.div(&two_pi).expect("2pi is non-zero").round_integer();
+        Ok(x.sub(&two_pi.mul(&Decimal::from_integer(turns, work))?))
+    }
+
+    /// Trig results smaller than the argument's own rounding error are noise, so `sin(pi)`
+    /// prints as 0 rather than as the error in the stored value of pi. An argument that fits in
+    /// the requested digits without touching the guard digits is taken as exact, so `sin 1e500`
+    /// keeps its value.
+    fn snap_to_zero(result: Decimal, argument: &Decimal) -> Decimal {
+        if digit_count(&argument.mantissa) <= argument.digits {
+            return result;
+        }
+        // One unit in the last place of the argument, which bounds its rounding error.
+        let ulp = argument.magnitude() - argument.precision() as i64 + 1;
+        if !result.is_zero() && result.magnitude() < ulp {
+            Decimal::from_i64(0, result.digits)
+        } else {
+            result
+        }
+    }
+
+    pub fn sin(&self) -> Result<Self, String> {
+        if self.is_zero() {
+            return Ok(self.clone());
+        }
+        let result = sin_series(&self.reduce_angle()?).with_digits(self.digits);
+        Ok(Decimal::snap_to_zero(result, self))
+    }
+
+    pub fn cos(&self) -> Result<Self, String> {
+        let result = cos_series(&self.reduce_angle()?).with_digits(self.digits);
+        Ok(Decimal::snap_to_zero(result, self))
+    }
+
+    pub fn tan(&self) -> Result<Self, String> {
+        let cos = self.cos()?;
+        if cos.is_zero() {
+            return Err("Tangent is undefined at this angle!".to_string());
+        }
+        self.sin()?.div(&cos)
+    }
+
+    pub fn atan(&self) -> Self {
+        if self.is_zero() {
+            return self.clone();
+        }
+        let work = self.digits + 5;
+        let one = Decimal::one(work);
+        let x = self.with_digits(work);
+
+        // atan(x) = ±pi/2 - atan(1/x) for |x| > 1.
+        if x.abs().compare(&one) == Ordering::Greater {
+            let half_pi = Decimal::pi(work).div_i64(2);
+            let inner = one.div(&x).expect("x is non-zero").atan();
+            let result = if x.is_negative() {
+                half_pi.neg().sub(&inner)
+            } else {
+                half_pi.sub(&inner)
+            };
+            return result.with_digits(self.digits);
+        }
+
+        // Halve the angle twice via atan(x) = 2 atan(x / (1 + sqrt(1 + x^2))).
+        let mut x = x;
+        for _ in 0..2 {
+            let root = one.add(&x.product(&x)).sqrt().expect("1 + x^2 is positive");
+            x = x.div(&one.add(&root)).expect("1 + sqrt(..) is positive");
+        }
+        atan_series(&x).mul_i64(4).with_digits(self.digits)
+    }
+
+    pub fn asin(&self) -> Result<Self, String> {
+        let work = self.digits + 5;
+        let one = Decimal::one(work);
+        let x = self.with_digits(work);
+        match x.abs().compare(&one) {
+            Ordering::Greater => {
+                Err("Cannot calculate arcsine of value outside [-1, 1]!".to_string())
+            }
+            Ordering::Equal => {
+                let half_pi = Decimal::pi(work).div_i64(2);
+                let result = if x.is_negative() {
+                    half_pi.neg()
+                } else {
+                    half_pi
+                };
+                Ok(result.with_digits(self.digits))
+            }
+            Ordering::Less => {
+                let root = one.sub(&x.product(&x)).sqrt()?;
+                Ok(x.div(&root)?.atan().with_digits(self.digits))
+            }
+        }
+    }
+
+    pub fn acos(&self) -> Result<Self, String> {
+        if self.abs().compare(&Decimal::one(self.digits)) == Ordering::Greater {
+            return Err("Cannot calculate arccosine of value outside [-1, 1]!".to_string());
+        }
+        let work = self.digits + 5;
+        let half_pi = Decimal::pi(work).div_i64(2);
+        Ok(half_pi
+            .sub(&self.with_digits(work).asin()?)
+            .with_digits(self.digits))
+    }
+
+    /// Angle of the point (x, y) = (other, self) in radians, in (-pi, pi].
+    pub fn atan2(&self, x: &Decimal) -> Result<Self, String> {
+        let y = self;
+        let digits = y.digits.max(x.digits);
+        let work = digits + 5;
+        let pi = Decimal::pi(work);
+        if x.is_zero() {
+            return match y.mantissa.sign() {
+                Sign::NoSign => Err("atan2 is undefined for (0, 0)!".to_string()),
+                Sign::Plus => Ok(pi.div_i64(2).with_digits(digits)),
+                Sign::Minus => Ok(pi.div_i64(2).neg().with_digits(digits)),
+            };
+        }
+        let base = y.with_digits(work).div(&x.with_digits(work))?.atan();
+        let result = if !x.is_negative() {
+            base
+        } else if y.is_negative() {
+            base.sub(&pi)
+        } else {
+            base.add(&pi)
+        };
+        Ok(result.with_digits(digits))
+    }
+
+    /// Working copy with enough extra digits to survive cancellation near zero.
+    fn for_cancellation(&self) -> Self {
+        let extra = (-self.magnitude()).max(0) as usize;
+        self.with_digits(self.digits + 5 + extra)
+    }
+
+    /// Whether sinh, tanh and their inverses, which differ from their argument by a relative
+    /// x^2 / 3 at most, round to the argument itself; the extra digits `for_cancellation`
+    /// would need are then more than could be stored.
+    fn is_below_precision(&self) -> bool {
+        !self.is_zero() && self.magnitude() < -(self.precision() as i64)
+    }
+
+    /// Whether `x^2` is negligible against 1 the other way round, so asinh(x) and acosh(x) are
+    /// ln(2x) to working precision without squaring x.
+    fn is_above_precision(&self) -> bool {
+        self.magnitude() > self.precision() as i64
+    }
+
+    pub fn sinh(&self) -> Result<Self, String> {
+        if self.is_below_precision() {
+            return Ok(self.clone());
+        }
+        let x = self.for_cancellation();
+        let a = x.exp()?;
+        let b = x.neg().exp()?;
+        Ok(a.sub(&b).div_i64(2).with_digits(self.digits))
+    }
+
+    pub fn cosh(&self) -> Result<Self, String> {
+        let x = self.with_digits(self.digits + 5);
+        let a = x.exp()?;
+        let b = x.neg().exp()?;
+        Ok(a.add(&b).div_i64(2).with_digits(self.digits))
+    }
+
+    pub fn tanh(&self) -> Result<Self, String> {
+        if self.is_below_precision() {
+            return Ok(self.clone());
+        }
+        let x = self.for_cancellation();
+        // tanh saturates long before exp overflows.
+        if x.abs().to_f64() > 1e4 {
+            let one = Decimal::one(self.digits);
+            return Ok(if x.is_negative() { one.neg() } else { one });
+        }
+        Ok(x.sinh()?.div(&x.cosh()?)?.with_digits(self.digits))
+    }
+
+    pub fn asinh(&self) -> Result<Self, String> {
+        if self.is_below_precision() {
+            return Ok(self.clone());
+        }
+        let x = self.for_cancellation().abs();
+        let one = Decimal::one(x.digits);
+        let magnitude = if x.is_above_precision() {
+            x.mul_i64(2).ln()?
+        } else {
+            x.add(&x.mul(&x)?.add(&one).sqrt()?).ln()?
+        };
+        let result = if self.is_negative() {
+            magnitude.neg()
+        } else {
+            magnitude
+        };
+        Ok(result.with_digits(self.digits))
+    }
+
+    pub fn acosh(&self) -> Result<Self, String> {
+        let x = self.with_digits(self.digits + 5);
+        let one = Decimal::one(x.digits);
+        if x.compare(&one) == Ordering::Less {
+            return Err("Cannot calculate inverse hyperbolic cosine of value below 1!".to_string());
+        }
+        let result = if x.is_above_precision() {
+            x.mul_i64(2).ln()?
+        } else {
+            x.add(&x.mul(&x)?.sub(&one).sqrt()?).ln()?
+        };
+        Ok(result.with_digits(self.digits))
+    }
+
+    pub fn atanh(&self) -> Result<Self, String> {
+        if self.is_below_precision() {
+            return Ok(self.clone());
+        }
+        let x = self.for_cancellation();
+        let one = Decimal::one(x.digits);
+        if x.abs().compare(&one) != Ordering::Less {
+            return Err(
+                "Inverse hyperbolic tangent only defined for values in (-1, 1)!".to_string(),
+            );
+        }
+        Ok(one
+            .add(&x)
+            .div(&one.sub(&x))?
+            .ln()?
+            .div_i64(2)
+            .with_digits(self.digits))
+    }
+
+    pub fn gamma(&self) -> Result<Self, String> {
+        self.check_gamma_pole("Gamma function")?;
+        if let Some(n) = self.to_integer().and_then(|n| n.to_u64()) {
+            if n <= MAX_EXACT_POWER {
+                return Ok(Decimal::from_integer(factorial(n - 1), self.digits));
+            }
+        }
+        let work = self.digits + 5;
+        let x = self.with_digits(work);
+        if x.compare(&Decimal::one(work).div_i64(2)) == Ordering::Less {
+            // Reflection formula: Γ(x)Γ(1 - x) = π / sin(πx).
+            let pi = Decimal::pi(work);
+            let denominator = pi
+                .mul(&x)?
+                .sin()?
+                .mul(&Decimal::one(work).sub(&x).gamma()?)?;
+            return Ok(pi.div(&denominator)?.with_digits(self.digits));
+        }
+        Ok(ln_gamma_spouge(&x)?.exp()?.with_digits(self.digits))
+    }
+
+    /// Natural logarithm of |Γ(x)|.
+    pub fn lgamma(&self) -> Result<Self, String> {
+        self.check_gamma_pole("Log-gamma function")?;
+        let work = self.digits + 5;
+        let x = self.with_digits(work);
+        if x.compare(&Decimal::one(work).div_i64(2)) == Ordering::Less {
+            let pi = Decimal::pi(work);
+            let reflected = pi.div(&pi.mul(&x)?.sin()?.abs())?.ln()?;
+            let rest = Decimal::one(work).sub(&x).lgamma()?;
+            return Ok(reflected.sub(&rest).with_digits(self.digits));
+        }
+        Ok(ln_gamma_spouge(&x)?.with_digits(self.digits))
+    }
+
+    fn check_gamma_pole(&self, function: &str) -> Result<(), String> {
+        if !self.mantissa.is_positive() && self.is_integer() {
+            Err(format!("{} undefined at non-positive integers!", function))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Sums a power series until terms stop affecting the result at working precision.
+/// `next_term` receives the previous term and the index of the term to produce.
+fn sum_series(first: Decimal, mut next_term: impl FnMut(&Decimal, i64) -> Decimal) -> Decimal {
+    let mut sum = first.clone();
+    let mut term = first;
+    for k in 1.. {
+        term = next_term(&term, k);
+        if term.is_zero() || term.is_negligible_against(&sum) {
+            break;
+        }
+        sum = sum.add(&term);
+    }
+    sum
+}
+
+/// exp(x) by Taylor series; intended for small |x|.
+fn exp_series(x: &Decimal) -> Decimal {
+    sum_series(Decimal::one(x.digits), |term, k| term.product(x).div_i64(k))
+}
+
+/// sin(x) by Taylor series; intended for |x| <= pi.
+fn sin_series(x: &Decimal) -> Decimal {
+    let x2 = x.product(x);
+    sum_series(x.clone(), |term, k| {
+        term.product(&x2).div_i64((2 * k) * (2 * k + 1)).neg()
+    })
+}
+
+/// cos(x) by Taylor series; intended for |x| <= pi.
+fn cos_series(x: &Decimal) -> Decimal {
+    let x2 = x.product(x);
+    sum_series(Decimal::one(x.digits), |term, k| {
+        term.product(&x2).div_i64((2 * k - 1) * (2 * k)).neg()
+    })
+}
+
+/// atan(x) = x - x^3/3 + x^5/5 - ...; intended for small |x|.
+fn atan_series(x: &Decimal) -> Decimal {
+    odd_power_series(x, true)
+}
+
+/// atanh(x) = x + x^3/3 + x^5/5 + ...; intended for small |x|.
+fn atanh_series(x: &Decimal) -> Decimal {
+    odd_power_series(x, false)
+}
+
+fn odd_power_series(x: &Decimal, alternating: bool) -> Decimal {
+    let x2 = if alternating {
+        x.product(x).neg()
+    } else {
+        x.product(x)
+    };
+    let mut power = x.clone();
+    let mut sum = x.clone();
+    for k in 1.. {
+        power = power.product(&x2);
+        let term = power.div_i64(2 * k + 1);
+        if term.is_zero() || term.is_negligible_against(&sum) {
+            break;
+        }
+        sum = sum.add(&term);
+    }
+    sum
+}
+
+/// atan(1/n) for an integer n > 1, used by Machin's formula.
+fn atan_inverse(n: i64, digits: usize) -> Decimal {
+    let x = Decimal::one(digits).div_i64(n);
+    atan_series(&x)
+}
+
+/// ln Γ(x) for x >= 1/2 using Spouge's approximation, whose parameter `a` is chosen so the
+/// truncation error is below the working precision.
+fn ln_gamma_spouge(x: &Decimal) -> Result<Decimal, String> {
+    let a = (x.precision() as f64 * 1.26).ceil() as i64 + 2;
+    let work = x.digits + a as usize;
+    let z = x.with_digits(work).sub(&Decimal::one(work));
+    let a_decimal = Decimal::from_i64(a, work);
+
+    // c_k = (-1)^(k-1) / (k-1)! * (a-k)^(k-1/2) * e^(a-k), built from exact integer powers
+    // and a table of powers of e so that no transcendental function is evaluated per term.
+    let e = Decimal::e(work);
+    let mut e_powers = vec![Decimal::one(work)];
+    for j in 1..a as usize {
+        let next = e_powers[j - 1].product(&e);
+        e_powers.push(next);
+    }
+
+    let mut sum = Decimal::pi(work).mul_i64(2).sqrt()?;
+    for k in 1..a {
+        let base = BigInt::from(a - k);
+        let ratio = Decimal::from_integer(base.pow(k as u32 - 1), work)
+            .div(&Decimal::from_integer(factorial(k as u64 - 1), work))?;
+        let mut coefficient = ratio
+            .product(&Decimal::from_integer(base, work).sqrt()?)
+            .product(&e_powers[(a - k) as usize]);
+        if k % 2 == 0 {
+            coefficient = coefficient.neg();
+        }
+        sum = sum.add(&coefficient.div(&z.add(&Decimal::from_i64(k, work)))?);
+    }
+
+    let half = Decimal::one(work).div_i64(2);
+    let shifted = z.add(&a_decimal);
+    let result = z
+        .add(&half)
+        .mul(&shifted.ln()?)?
+        .sub(&shifted)
+        .add(&sum.ln()?);
+    Ok(result.with_digits(x.digits))
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        let (mut mantissa, mut exponent) =
+            round_to(self.mantissa.clone(), self.exponent, self.digits);
+        let ten = BigInt::from(10u32);
+        while (&mantissa % &ten).is_zero() {
+            mantissa /= &ten;
+            exponent += 1;
+        }
+
+        let sign = if mantissa.is_negative() { "-" } else { "" };
+        let digits = mantissa.magnitude().to_string();
+        let magnitude = exponent + digits.len() as i64 - 1;
+
+        if magnitude < -7 || magnitude >= (self.digits as i64).max(21) {
+            let (first, rest) = digits.split_at(1);
+            if rest.is_empty() {
+                write!(f, "{}{}e{}", sign, first, magnitude)
+            } else {
+                write!(f, "{}{}.{}e{}", sign, first, rest, magnitude)
+            }
+        } else if exponent >= 0 {
+            write!(f, "{}{}{}", sign, digits, "0".repeat(exponent as usize))
+        } else {
+            let point = digits.len() as i64 + exponent;
+            if point > 0 {
+                let (whole, fraction) = digits.split_at(point as usize);
+                write!(f, "{}{}.{}", sign, whole, fraction)
+            } else {
+                write!(f, "{}0.{}{}", sign, "0".repeat((-point) as usize), digits)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(text: &str, digits: usize) -> Decimal {
+        Decimal::parse(text, digits).unwrap()
+    }
+
+    #[test]
+    fn parses_literals_exactly() {
+        let x = decimal("1.00000000000000000001", 50);
+        assert_eq!(x.sub(&Decimal::one(50)).to_string(), "1e-20");
+        let n = decimal("12345678901234567890123", 50);
+        assert_eq!(
+            n.add(&Decimal::one(50)).to_string(),
+            "12345678901234567890124"
+        );
+    }
+
+    #[test]
+    fn parses_rationals_from_digits() {
+        let eighth = BigRational::new(BigInt::from(1), BigInt::from(8));
+        assert_eq!(parse_rational("0.125"), Some(eighth));
+        assert_eq!(
+            parse_rational("1.5e3"),
+            Some(BigRational::from_integer(1500.into()))
+        );
+        assert_eq!(parse_rational("1e1000000000"), None);
+    }
+
+    #[test]
+    fn formats_like_floats() {
+        assert_eq!(decimal("123.45", 10).to_string(), "123.45");
+        assert_eq!(decimal("0.00012", 10).to_string(), "0.00012");
+        assert_eq!(decimal("1e-8", 10).to_string(), "1e-8");
+        assert_eq!(decimal("-2.5e30", 10).to_string(), "-2.5e30");
+        assert_eq!(decimal("2.71828", 3).to_string(), "2.72");
+        assert_eq!(decimal("0", 10).to_string(), "0");
+    }
+
+    #[test]
+    fn computes_logarithms_and_exponentials() {
+        assert_eq!(
+            decimal("2", 30).ln().unwrap().to_string(),
+            "0.693147180559945309417232121458"
+        );
+        assert_eq!(
+            decimal("1", 30).exp().unwrap().to_string(),
+            "2.71828182845904523536028747135"
+        );
+        assert_eq!(decimal("1000", 20).log10().unwrap().to_string(), "3");
+        assert!(decimal("-1", 20).ln().is_err());
+    }
+
+    #[test]
+    fn computes_arctangents() {
+        assert_eq!(
+            decimal("1", 30).atan().mul_i64(4).to_string(),
+            Decimal::pi(30).to_string()
+        );
+        assert_eq!(
+            decimal("0.5", 25).atan().to_string(),
+            "0.4636476090008061162142562"
+        );
+    }
+
+    #[test]
+    fn computes_gamma() {
+        assert_eq!(decimal("5", 20).gamma().unwrap().to_string(), "24");
+        assert_eq!(
+            decimal("0.5", 20).gamma().unwrap().to_string(),
+            "1.7724538509055160273"
+        );
+        assert!(decimal("-2", 20).gamma().is_err());
+    }
+
+    #[test]
+    fn snaps_trig_noise_to_zero() {
+        for digits in [5, 20, 50] {
+            assert_eq!(Decimal::pi(digits).sin().unwrap().to_string(), "0");
+            let right_angle = Decimal::pi(digits).div_i64(2);
+            assert_eq!(right_angle.cos().unwrap().to_string(), "0");
+        }
+    }
+
+    #[test]
+    fn keeps_trig_of_exact_large_arguments() {
+        assert_eq!(decimal("1e10", 5).sin().unwrap().to_string(), "-0.48751");
+        assert_eq!(
+            decimal("1e10", 10).sin().unwrap().to_string(),
+            "-0.4875060251"
+        );
+        assert!(decimal("1e2000", 10).sin().is_err());
+    }
+
+    #[test]
+    fn reports_results_out_of_range() {
+        let out_of_range = Err("Result out of range".to_string());
+        let huge = decimal("1e1000000000000000000", 20);
+        let tiny = decimal("1e-1000000000000000000", 20);
+        assert_eq!(Decimal::parse("1e5000000000000000000", 20), out_of_range);
+        assert_eq!(huge.mul(&huge), out_of_range);
+        assert_eq!(tiny.div(&huge), out_of_range);
+        assert_eq!(huge.pow(&decimal("2", 20)), out_of_range);
+        assert_eq!(tiny.pow(&decimal("-1", 20)), Ok(huge.clone()));
+        assert_eq!(huge.mul(&tiny).unwrap().to_string(), "1");
+    }
+
+    #[test]
+    fn keeps_tiny_and_huge_hyperbolic_arguments_in_range() {
+        let tiny = decimal("1e-1000000000000000000", 20);
+        assert_eq!(tiny.sinh(), Ok(tiny.clone()));
+        assert_eq!(tiny.atanh(), Ok(tiny.clone()));
+        let huge = decimal("1e1000000000000000000", 20);
+        assert_eq!(huge.asinh().unwrap().to_string(), "2302585092994045684.7");
+    }
+
+    #[test]
+    fn bounds_exact_integers() {
+        assert_eq!(decimal("1.5e3", 10).to_integer(), Some(BigInt::from(1500)));
+        assert_eq!(decimal("1.5", 10).to_integer(), None);
+        assert_eq!(decimal("1e1000000000", 10).to_integer(), None);
+    }
+}
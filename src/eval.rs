@@ -0,0 +1,1015 @@
+use crate::ast::{BinaryOp, Expr, UnaryOp};
+use crate::calculator::{AngleMode, Calculator, HistoryRef, NumberMode};
+use crate::combinatorics;
+use crate::complex;
+use crate::constants;
+use crate::decimal::{self, Decimal};
+use crate::matrix::{self, Matrix};
+use crate::numeric;
+use crate::polynomial;
+use crate::programmer::{self, Word};
+use crate::special;
+use crate::stats;
+use crate::symbolic;
+use crate::units;
+use crate::value::Value;
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use num_rational::BigRational;
+use num_traits::{Signed, ToPrimitive, Zero};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+const BUILTINS: &[&str] = &[
+    "sqrt", "sin", "cos", "tan", "asin", "acos", "atan", "atan2", "sec", "csc", "cot", "sinh",
+    "cosh", "tanh", "asinh", "acosh", "atanh", "log", "ln", "abs", "sign", "gamma", "lgamma",
+    "digamma",
+];
+
+/// Exact integer powers whose result would exceed this many bits fall back to floating point.
+const MAX_EXACT_POWER_BITS: u64 = 1 << 22;
+
+/// Reciprocal trig functions are undefined where the underlying function is (numerically) zero.
+const RECIPROCAL_EPSILON: f64 = 1e-12;
+
+pub fn is_builtin(name: &str) -> bool {
+    BUILTINS.contains(&name)
+        || combinatorics::FUNCTIONS.contains(&name)
+        || complex::FUNCTIONS.contains(&name)
+        || stats::FUNCTIONS.contains(&name)
+        || matrix::FUNCTIONS.contains(&name)
+        || polynomial::FUNCTIONS.contains(&name)
+        || numeric::FUNCTIONS.contains(&name)
+        || symbolic::FUNCTIONS.contains(&name)
+}
+
+/// Maximum nesting of user function calls before evaluation is aborted. Without conditionals
+/// a recursive definition never terminates, so only chains of distinct functions nest
+/// legitimately. Debug builds take several kilobytes of stack per call, so a higher limit
+/// would overflow a 2 MB thread before the check ever fired.
+const MAX_CALL_DEPTH: usize = 64;
+
+pub fn calculate(expr: &Expr, calc: &Calculator) -> Result<Value, String> {
+    calculate_with(expr, calc, HashMap::new())
+}
+
+/// Evaluates the arguments of `integrate(expr, x, a, b)`, returning the integral along with
+/// its error estimate so the REPL can show both.
+pub fn integrate(args: &[Expr], calc: &Calculator) -> Result<(Value, f64), String> {
+    let (value, error) = Evaluator {
+        calc,
+        locals: HashMap::new(),
+        depth: 0,
+    }
+    .integrate(args)?;
+    Ok((Value::Real(value), error))
+}
+
+/// Evaluates `expr` with some names bound to values, shadowing variables, constants and units;
+/// this is how solvers substitute trial values for their unknowns.
+pub fn calculate_with(
+    expr: &Expr,
+    calc: &Calculator,
+    locals: HashMap<String, Value>,
+) -> Result<Value, String> {
+    Evaluator {
+        calc,
+        locals,
+        depth: 0,
+    }
+    .eval(expr)
+}
+
+/// Walks an expression tree. `locals` holds the parameters of the user function being
+/// evaluated; function bodies only see their own parameters plus global state.
+struct Evaluator<'a> {
+    calc: &'a Calculator,
+    locals: HashMap<String, Value>,
+    depth: usize,
+}
+
+impl Evaluator<'_> {
+    fn eval(&self, expr: &Expr) -> Result<Value, String> {
+        match expr {
+            Expr::Number(digits) => {
+                let value = digits.parse().unwrap_or(f64::NAN);
+                match self.calc.number_mode {
+                    NumberMode::Float => Ok(Value::Real(value)),
+                    NumberMode::Decimal(precision) => {
+                        Decimal::parse(digits, precision).map(Value::Decimal)
+                    }
+                    // Literals too large to write out exactly stay floating point.
+                    NumberMode::Fraction => {
+                        Ok(decimal::parse_rational(digits)
+                            .map_or(Value::Real(value), Value::Rational))
+                    }
+                    NumberMode::Complex => Ok(Value::Complex(Complex64::new(value, 0.0))),
+                    NumberMode::Programmer => match integer_literal(expr) {
+                        Some(n) => self.fit(n),
+                        None => Ok(Value::Real(value)),
+                    },
+                }
+            }
+            Expr::Integer(n) => match (self.calc.number_mode, self.word()) {
+                (NumberMode::Decimal(digits), _) => {
+                    Ok(Value::Decimal(Decimal::from_integer(n.clone(), digits)))
+                }
+                (_, Some(word)) => word
+                    .fit_pattern(n.clone(), self.calc.overflow)
+                    .map(Value::Integer),
+                (_, None) => Ok(Value::Integer(n.clone())),
+            },
+            Expr::Ident(name) => self.lookup(name),
+            Expr::Unit(name) => self
+                .lookup(name)
+                .or_else(|e| units::lookup(name).map(Value::Quantity).ok_or(e)),
+            Expr::Unary(UnaryOp::Negate, operand) => {
+                // `-128` is a literal of its own in an i8, not the negation of an out-of-range 128.
+                if let (Some(_), Some(n)) = (self.word(), integer_literal(operand)) {
+                    return self.fit(-n);
+                }
+                let value = self.eval(operand)?;
+                match (self.word(), value.to_integer()) {
+                    (Some(_), Some(n)) => self.fit(-n),
+                    _ => Ok(negate(value)),
+                }
+            }
+            Expr::Unary(UnaryOp::Not, operand) => {
+                Ok(self.wrap(programmer::not(&self.eval(operand)?)?))
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let a = self.eval(lhs)?;
+                let b = self.eval(rhs)?;
+                if let (Some(word), Some(x), Some(y)) =
+                    (self.word(), a.to_integer(), b.to_integer())
+                {
+                    return programmer::apply_word(*op, &x, &y, word, self.calc.overflow)
+                        .map(Value::Integer);
+                }
+                apply_binary(*op, a, b)
+            }
+            Expr::Bitwise(op, lhs, rhs) => {
+                Ok(self.wrap(programmer::apply(*op, &self.eval(lhs)?, &self.eval(rhs)?)?))
+            }
+            Expr::Call(name, args) if name == "roots" => self.roots(args),
+            Expr::Call(name, args) if name == "nsolve" => self.nsolve(args),
+            Expr::Call(name, args) if name == "integrate" => {
+                self.integrate(args).map(|(value, _)| Value::Real(value))
+            }
+            Expr::Call(name, args) if name == "diff" => self.diff(args),
+            Expr::Call(name, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.call(name, args)
+            }
+            Expr::List(items) => {
+                let items = items
+                    .iter()
+                    .map(|item| self.eval(item))
+                    .collect::<Result<Vec<_>, _>>()?;
+                list_or_matrix(items)
+            }
+            Expr::Convert(value, unit) => units::convert(self.eval(value)?, unit),
+        }
+    }
+
+    /// The fixed-width integer type in effect, if any; word sizes only apply in `mode prog`.
+    fn word(&self) -> Option<Word> {
+        match self.calc.number_mode {
+            NumberMode::Programmer => self.calc.word,
+            _ => None,
+        }
+    }
+
+    /// Brings an integer result into the word's range according to the overflow mode.
+    fn fit(&self, n: BigInt) -> Result<Value, String> {
+        match self.word() {
+            Some(word) => word.fit(n, self.calc.overflow).map(Value::Integer),
+            None => Ok(Value::Integer(n)),
+        }
+    }
+
+    /// Truncates a bitwise result to the word; bit operations never overflow, they drop bits.
+    fn wrap(&self, value: Value) -> Value {
+        match (self.word(), value) {
+            (Some(word), Value::Integer(n)) => Value::Integer(word.wrap(&n)),
+            (_, value) => value,
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Result<Value, String> {
+        if let Some(value) = self.locals.get(name) {
+            return Ok(value.clone());
+        }
+        if let Some(reference) = HistoryRef::parse(name) {
+            return self.calc.history_result(reference);
+        }
+        if let Some(value) = self.calc.variable(name) {
+            return Ok(value.clone());
+        }
+        if let Some(constant) = constants::lookup(name) {
+            return constant.value(self.calc.number_mode);
+        }
+        match (name, self.calc.number_mode) {
+            ("i", NumberMode::Complex) => Ok(Value::Complex(Complex64::i())),
+            _ if units::lookup(name).is_some() => Err(format!(
+                "Unknown variable '{}' (units follow a number, as in 1 {})",
+                name, name
+            )),
+            _ => Err(format!("Unknown variable '{}'", name)),
+        }
+    }
+
+    /// `roots(1, -3, 2)` takes coefficients, highest power first; `roots(x^3 - 1, x)` reads its
+    /// first argument as a polynomial in the named variable instead of evaluating it.
+    fn roots(&self, args: &[Expr]) -> Result<Value, String> {
+        let coefficients = match args {
+            [polynomial, Expr::Ident(var)] if polynomial.identifiers().contains(var) => {
+                polynomial::expand(polynomial, var, &|expr| self.eval(expr))?
+            }
+            [] => return Err("Usage: roots(a, b, c, ...) or roots(expr, x)".to_string()),
+            _ => args
+                .iter()
+                .rev()
+                .map(|arg| polynomial::coefficient(&self.eval(arg)?))
+                .collect::<Result<_, _>>()?,
+        };
+        polynomial::roots(coefficients).map(Value::List)
+    }
+
+    /// `nsolve(expr, x, guess)` runs Newton's method from `guess`; `nsolve(expr, x, a, b)` runs
+    /// Brent's method on a bracket where `expr` changes sign.
+    fn nsolve(&self, args: &[Expr]) -> Result<Value, String> {
+        match args {
+            [expr, Expr::Ident(var), guess] => {
+                numeric::newton(&self.bind(expr, var), self.real(guess)?)
+            }
+            [expr, Expr::Ident(var), a, b] => {
+                numeric::brent(&self.bind(expr, var), self.real(a)?, self.real(b)?)
+            }
+            _ => Err("Usage: nsolve(expr, x, guess) or nsolve(expr, x, a, b)".to_string()),
+        }
+        .map(Value::Real)
+    }
+
+    /// `integrate(expr, x, a, b)`, returning the integral and its error estimate. Either bound
+    /// may be `inf` or `-inf`.
+    fn integrate(&self, args: &[Expr]) -> Result<(f64, f64), String> {
+        match args {
+            [expr, Expr::Ident(var), a, b] => {
+                numeric::integrate(&self.bind(expr, var), self.real(a)?, self.real(b)?)
+            }
+            _ => Err("Usage: integrate(expr, x, a, b)".to_string()),
+        }
+    }
+
+    /// `diff(expr, x)` evaluates the symbolic derivative as it stands, so it can define a user
+    /// function; `diff(expr, x, at)` evaluates it with `x` bound to `at`.
+    fn diff(&self, args: &[Expr]) -> Result<Value, String> {
+        match args {
+            [_, _] => self.eval(&symbolic::differentiate(args, self.calc)?),
+            [_, Expr::Ident(var), at] => {
+                let derivative = symbolic::differentiate(&args[..2], self.calc)?;
+                let mut locals = self.locals.clone();
+                locals.insert(var.clone(), self.eval(at)?);
+                Evaluator {
+                    calc: self.calc,
+                    locals,
+                    depth: self.depth,
+                }
+                .eval(&derivative)
+            }
+            _ => Err("Usage: diff(expr, x) or diff(expr, x, at)".to_string()),
+        }
+    }
+
+    /// `expr` as a real function of `var`, which is bound on top of the current locals.
+    fn bind<'e>(
+        &'e self,
+        expr: &'e Expr,
+        var: &'e str,
+    ) -> impl Fn(f64) -> Result<f64, String> + 'e {
+        move |x| {
+            let mut locals = self.locals.clone();
+            locals.insert(var.to_string(), Value::Real(x));
+            let evaluator = Evaluator {
+                calc: self.calc,
+                locals,
+                depth: self.depth,
+            };
+            match evaluator.eval(expr)? {
+                Value::Quantity(_) => Err(format!("'{}' must be dimensionless", expr)),
+                value => Ok(value.to_f64()),
+            }
+        }
+    }
+
+    /// Evaluates a numeric argument such as a starting guess or a bound.
+    fn real(&self, expr: &Expr) -> Result<f64, String> {
+        match self.eval(expr)? {
+            Value::Quantity(_) => Err(format!("'{}' must be dimensionless", expr)),
+            value if value.to_f64().is_nan() => Err(format!("'{}' must be a real number", expr)),
+            value => Ok(value.to_f64()),
+        }
+    }
+
+    fn call(&self, name: &str, args: Vec<Value>) -> Result<Value, String> {
+        let Some(function) = self.calc.function(name) else {
+            return call_builtin(name, &args, self.calc.angle_mode);
+        };
+
+        if args.len() != function.params.len() {
+            return Err(format!(
+                "Function '{}' expects {} argument(s), got {}",
+                name,
+                function.params.len(),
+                args.len()
+            ));
+        }
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(format!(
+                "Maximum recursion depth ({}) exceeded in '{}'",
+                MAX_CALL_DEPTH, name
+            ));
+        }
+
+        Evaluator {
+            calc: self.calc,
+            locals: function.params.iter().cloned().zip(args).collect(),
+            depth: self.depth + 1,
+        }
+        .eval(&function.body)
+    }
+}
+
+/// A list of lists is a matrix literal, one inner list per row: `[[1, 2], [3, 4]]`.
+fn list_or_matrix(items: Vec<Value>) -> Result<Value, String> {
+    if !items.iter().any(|item| matches!(item, Value::List(_))) {
+        return Ok(Value::List(items));
+    }
+    let rows = items
+        .into_iter()
+        .map(|item| match item {
+            Value::List(row) => Ok(row),
+            _ => Err("Every row of a matrix must be a list".to_string()),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Matrix::from_rows(&rows).map(Value::Matrix)
+}
+
+/// The exact integer a literal such as `128`, `1e3` or `0x80` denotes, if it is one.
+fn integer_literal(expr: &Expr) -> Option<BigInt> {
+    match expr {
+        Expr::Number(digits) => decimal::parse_rational(digits)
+            .filter(BigRational::is_integer)
+            .map(|r| r.to_integer()),
+        Expr::Integer(n) => Some(n.clone()),
+        _ => None,
+    }
+}
+
+fn negate(value: Value) -> Value {
+    match value {
+        Value::Real(x) => Value::Real(-x),
+        Value::Integer(n) => Value::Integer(-n),
+        Value::Decimal(d) => Value::Decimal(d.neg()),
+        Value::Rational(r) => Value::Rational(-r),
+        // Subtracting from zero keeps a real's imaginary part at +0, so `sqrt(-4)` and `ln(-1)`
+        // land on the principal side of their branch cuts.
+        Value::Complex(z) => Value::Complex(Complex64::new(0.0, 0.0) - z),
+        Value::List(items) => Value::List(items.into_iter().map(negate).collect()),
+        Value::Matrix(m) => Value::Matrix(m.scale(-1.0)),
+        Value::Quantity(q) => Value::Quantity(units::Quantity {
+            value: -q.value,
+            ..q
+        }),
+    }
+}
+
+/// The working precision when any of `values` is a decimal.
+fn decimal_digits(values: &[&Value]) -> Option<usize> {
+    values
+        .iter()
+        .filter_map(|value| match value {
+            Value::Decimal(d) => Some(d.digits()),
+            _ => None,
+        })
+        .max()
+}
+
+/// Quantities, complex numbers and decimals absorb whatever they are combined with; rationals
+/// and exact integers stay exact when combined with other exact values; anything else is
+/// evaluated in floating point.
+fn apply_binary(op: BinaryOp, a: Value, b: Value) -> Result<Value, String> {
+    if matches!(a, Value::List(_)) || matches!(b, Value::List(_)) {
+        return Err("Lists can only be passed to statistics functions".to_string());
+    }
+    if matches!(a, Value::Matrix(_)) || matches!(b, Value::Matrix(_)) {
+        return matrix::apply_binary(op, &a, &b);
+    }
+    if matches!(a, Value::Quantity(_)) || matches!(b, Value::Quantity(_)) {
+        return units::apply_binary(op, a, b);
+    }
+    if matches!(a, Value::Complex(_)) || matches!(b, Value::Complex(_)) {
+        return complex::apply_binary(op, a.to_complex(), b.to_complex()).map(Value::Complex);
+    }
+    if let Some(digits) = decimal_digits(&[&a, &b]) {
+        let x = a.to_decimal(digits)?;
+        let y = b.to_decimal(digits)?;
+        return apply_decimal(op, &x, &y).map(Value::Decimal);
+    }
+    if matches!(a, Value::Rational(_)) || matches!(b, Value::Rational(_)) {
+        if let (Some(x), Some(y)) = (a.to_rational(), b.to_rational()) {
+            if let Some(result) = apply_rational(op, &x, &y)? {
+                return Ok(Value::Rational(result));
+            }
+        }
+    }
+    if matches!(a, Value::Integer(_)) || matches!(b, Value::Integer(_)) {
+        if let (Some(x), Some(y)) = (a.to_integer(), b.to_integer()) {
+            if let Some(result) = apply_integer(op, &x, &y)? {
+                return Ok(Value::Integer(result));
+            }
+        }
+    }
+    apply_real(op, a.to_f64(), b.to_f64()).map(Value::Real)
+}
+
+/// Returns `None` when the exact result is not an integer or would be unreasonably large.
+fn apply_integer(op: BinaryOp, a: &BigInt, b: &BigInt) -> Result<Option<BigInt>, String> {
+    Ok(match op {
+        BinaryOp::Add => Some(a + b),
+        BinaryOp::Subtract => Some(a - b),
+        BinaryOp::Multiply => Some(a * b),
+        BinaryOp::Divide => {
+            if b.is_zero() {
+                return Err("Division by zero!".to_string());
+            }
+            (a % b).is_zero().then(|| a / b)
+        }
+        BinaryOp::Power => b
+            .to_u32()
+            .filter(|&exp| a.bits().saturating_mul(exp as u64) <= MAX_EXACT_POWER_BITS)
+            .map(|exp| a.pow(exp)),
+    })
+}
+
+/// Returns `None` when the result is irrational (or too large to compute exactly), so the
+/// caller falls back to floating point.
+fn apply_rational(
+    op: BinaryOp,
+    a: &BigRational,
+    b: &BigRational,
+) -> Result<Option<BigRational>, String> {
+    Ok(match op {
+        BinaryOp::Add => Some(a + b),
+        BinaryOp::Subtract => Some(a - b),
+        BinaryOp::Multiply => Some(a * b),
+        BinaryOp::Divide => {
+            if b.is_zero() {
+                return Err("Division by zero!".to_string());
+            }
+            Some(a / b)
+        }
+        BinaryOp::Power => rational_power(a, b)?,
+    })
+}
+
+/// a^(p/q), exact when a^(1/q) is rational.
+fn rational_power(a: &BigRational, b: &BigRational) -> Result<Option<BigRational>, String> {
+    let (Some(p), Some(q)) = (b.numer().to_i32(), b.denom().to_u32()) else {
+        return Ok(None);
+    };
+    if a.is_zero() && p < 0 {
+        return Err("Division by zero!".to_string());
+    }
+    let Some(root) = rational_root(a, q) else {
+        return Ok(None);
+    };
+    let bits = root.numer().bits().max(root.denom().bits());
+    if bits.saturating_mul(p.unsigned_abs() as u64) > MAX_EXACT_POWER_BITS {
+        return Ok(None);
+    }
+    Ok(Some(root.pow(p)))
+}
+
+/// The exact q-th root of `a`, if it is rational.
+fn rational_root(a: &BigRational, q: u32) -> Option<BigRational> {
+    if q == 1 {
+        return Some(a.clone());
+    }
+    if a.is_negative() && q.is_multiple_of(2) {
+        return None;
+    }
+    let numer = a.numer().nth_root(q);
+    let denom = a.denom().nth_root(q);
+    (numer.pow(q) == *a.numer() && denom.pow(q) == *a.denom())
+        .then(|| BigRational::new(numer, denom))
+}
+
+fn apply_decimal(op: BinaryOp, a: &Decimal, b: &Decimal) -> Result<Decimal, String> {
+    match op {
+        BinaryOp::Add => Ok(a.add(b)),
+        BinaryOp::Subtract => Ok(a.sub(b)),
+        BinaryOp::Multiply => a.mul(b),
+        BinaryOp::Divide => a.div(b),
+        BinaryOp::Power => a.pow(b),
+    }
+}
+
+fn apply_real(op: BinaryOp, a: f64, b: f64) -> Result<f64, String> {
+    match op {
+        BinaryOp::Add => Ok(a + b),
+        BinaryOp::Subtract => Ok(a - b),
+        BinaryOp::Multiply => Ok(a * b),
+        BinaryOp::Divide => {
+            if b == 0.0 {
+                Err("Division by zero!".to_string())
+            } else {
+                Ok(a / b)
+            }
+        }
+        BinaryOp::Power => Ok(a.powf(b)),
+    }
+}
+
+fn call_builtin(name: &str, args: &[Value], angle_mode: AngleMode) -> Result<Value, String> {
+    if stats::FUNCTIONS.contains(&name) {
+        return stats::call(name, args);
+    }
+    if matrix::FUNCTIONS.contains(&name) {
+        return matrix::call(name, args);
+    }
+    if args.iter().any(|arg| matches!(arg, Value::List(_))) {
+        return Err(format!("'{}' does not accept lists", name));
+    }
+    if args.iter().any(|arg| matches!(arg, Value::Matrix(_))) {
+        return Err(format!("'{}' does not accept matrices", name));
+    }
+    if args.iter().any(|arg| matches!(arg, Value::Quantity(_))) {
+        return units::call(name, args);
+    }
+    if args.iter().any(|arg| matches!(arg, Value::Complex(_))) {
+        let zs: Vec<Complex64> = args.iter().map(Value::to_complex).collect();
+        if let Some(result) = complex::call(name, &zs, angle_mode) {
+            return result.map(Value::Complex);
+        }
+        // Real-only functions still accept complex values with no imaginary part.
+        if zs.iter().any(|z| z.im != 0.0) {
+            return Err(format!("'{}' does not support complex arguments", name));
+        }
+    }
+    if let Some(digits) = decimal_digits(&args.iter().collect::<Vec<_>>()) {
+        return call_decimal(name, args, digits, angle_mode);
+    }
+    if combinatorics::FUNCTIONS.contains(&name) {
+        return combinatorics::call(name, args);
+    }
+    if let [Value::Rational(r)] = args {
+        match name {
+            "abs" => return Ok(Value::Rational(r.abs())),
+            "sign" => return Ok(Value::Rational(r.signum())),
+            "sqrt" if !r.is_negative() => {
+                if let Some(root) = rational_root(r, 2) {
+                    return Ok(Value::Rational(root));
+                }
+            }
+            _ => {}
+        }
+    }
+    let args: Vec<f64> = args.iter().map(Value::to_f64).collect();
+    call_real(name, &args, angle_mode).map(Value::Real)
+}
+
+fn call_real(name: &str, args: &[f64], angle_mode: AngleMode) -> Result<f64, String> {
+    match (name, args) {
+        ("sqrt", &[a]) => {
+            if a < 0.0 {
+                Err("Cannot calculate square root of negative number!".to_string())
+            } else {
+                Ok(a.sqrt())
+            }
+        }
+        ("sin", &[a]) => Ok(angle_mode.to_radians(a).sin()),
+        ("cos", &[a]) => Ok(angle_mode.to_radians(a).cos()),
+        ("tan", &[a]) => Ok(angle_mode.to_radians(a).tan()),
+        ("asin", &[a]) => {
+            if !(-1.0..=1.0).contains(&a) {
+                Err("Cannot calculate arcsine of value outside [-1, 1]!".to_string())
+            } else {
+                Ok(angle_mode.convert_radians(a.asin()))
+            }
+        }
+        ("acos", &[a]) => {
+            if !(-1.0..=1.0).contains(&a) {
+                Err("Cannot calculate arccosine of value outside [-1, 1]!".to_string())
+            } else {
+                Ok(angle_mode.convert_radians(a.acos()))
+            }
+        }
+        ("atan", &[a]) => Ok(angle_mode.convert_radians(a.atan())),
+        ("atan2", &[y, x]) => {
+            if y == 0.0 && x == 0.0 {
+                Err("atan2 is undefined for (0, 0)!".to_string())
+            } else {
+                Ok(angle_mode.convert_radians(y.atan2(x)))
+            }
+        }
+        ("sec", &[a]) => reciprocal("Secant", angle_mode.to_radians(a).cos()),
+        ("csc", &[a]) => reciprocal("Cosecant", angle_mode.to_radians(a).sin()),
+        ("cot", &[a]) => {
+            // cos / sin rather than 1 / tan, so cot 90 is 0 instead of the error in tan 90.
+            let (sin, cos) = angle_mode.to_radians(a).sin_cos();
+            if sin.abs() < RECIPROCAL_EPSILON {
+                Err("Cotangent is undefined at this angle!".to_string())
+            } else if cos.abs() < RECIPROCAL_EPSILON {
+                Ok(0.0)
+            } else {
+                Ok(cos / sin)
+            }
+        }
+        ("sinh", &[a]) => Ok(a.sinh()),
+        ("cosh", &[a]) => Ok(a.cosh()),
+        ("tanh", &[a]) => Ok(a.tanh()),
+        ("asinh", &[a]) => Ok(a.asinh()),
+        ("acosh", &[a]) => {
+            if a < 1.0 {
+                Err("Cannot calculate inverse hyperbolic cosine of value below 1!".to_string())
+            } else {
+                Ok(a.acosh())
+            }
+        }
+        ("atanh", &[a]) => {
+            if a.abs() >= 1.0 {
+                Err("Inverse hyperbolic tangent only defined for values in (-1, 1)!".to_string())
+            } else {
+                Ok(a.atanh())
+            }
+        }
+        ("log", &[a]) => {
+            if a <= 0.0 {
+                Err("Cannot calculate logarithm of non-positive number!".to_string())
+            } else {
+                Ok(a.log10())
+            }
+        }
+        ("ln", &[a]) => {
+            if a <= 0.0 {
+                Err("Cannot calculate natural logarithm of non-positive number!".to_string())
+            } else {
+                Ok(a.ln())
+            }
+        }
+        ("abs", &[a]) => Ok(a.abs()),
+        ("sign", &[a]) => Ok(if a == 0.0 { 0.0 } else { a.signum() }),
+        ("re", &[a]) | ("conj", &[a]) => Ok(a),
+        ("im", &[_]) => Ok(0.0),
+        ("arg", &[a]) => {
+            if a == 0.0 {
+                Err("Argument of zero is undefined!".to_string())
+            } else if a < 0.0 {
+                Ok(angle_mode.convert_radians(PI))
+            } else {
+                Ok(0.0)
+            }
+        }
+        ("gamma", &[a]) => special::gamma(a),
+        ("lgamma", &[a]) => special::lgamma(a),
+        ("digamma", &[a]) => special::digamma(a),
+        (name, _) if is_builtin(name) => Err(format!(
+            "Wrong number of arguments for '{}' (got {})",
+            name,
+            args.len()
+        )),
+        (name, _) => Err(format!("Unknown function '{}'", name)),
+    }
+}
+
+fn reciprocal(function: &str, value: f64) -> Result<f64, String> {
+    if value.abs() < RECIPROCAL_EPSILON {
+        Err(format!("{} is undefined at this angle!", function))
+    } else {
+        Ok(1.0 / value)
+    }
+}
+
+fn decimal_to_radians(angle: &Decimal, angle_mode: AngleMode) -> Result<Decimal, String> {
+    let digits = angle.digits() + 5;
+    let half_turn = match angle_mode {
+        AngleMode::Radians => return Ok(angle.clone()),
+        AngleMode::Degrees => 180,
+        AngleMode::Gradians => 200,
+    };
+    let pi = Decimal::pi(digits);
+    Ok(angle
+        .mul(&pi)?
+        .div(&Decimal::from_i64(half_turn, digits))?
+        .with_digits(angle.digits()))
+}
+
+fn decimal_convert_radians(angle: Decimal, angle_mode: AngleMode) -> Result<Decimal, String> {
+    let digits = angle.digits() + 5;
+    let half_turn = match angle_mode {
+        AngleMode::Radians => return Ok(angle),
+        AngleMode::Degrees => 180,
+        AngleMode::Gradians => 200,
+    };
+    let pi = Decimal::pi(digits);
+    Ok(angle
+        .mul(&Decimal::from_i64(half_turn, digits))?
+        .div(&pi)?
+        .with_digits(angle.digits()))
+}
+
+fn decimal_reciprocal(function: &str, value: Decimal) -> Result<Decimal, String> {
+    if value.is_zero() {
+        return Err(format!("{} is undefined at this angle!", function));
+    }
+    Decimal::from_i64(1, value.digits()).div(&value)
+}
+
+/// Evaluates a built-in with arbitrary-precision decimal arguments.
+fn call_decimal(
+    name: &str,
+    args: &[Value],
+    digits: usize,
+    angle_mode: AngleMode,
+) -> Result<Value, String> {
+    if combinatorics::FUNCTIONS.contains(&name) && args.iter().all(|a| a.to_integer().is_some()) {
+        return combinatorics::call(name, args);
+    }
+    let args = args
+        .iter()
+        .map(|arg| arg.to_decimal(digits))
+        .collect::<Result<Vec<_>, _>>()?;
+    let radians = |a: &Decimal| decimal_to_radians(a, angle_mode);
+
+    let result = match (name, args.as_slice()) {
+        ("sqrt", [a]) => a.sqrt()?,
+        ("sin", [a]) => radians(a)?.sin()?,
+        ("cos", [a]) => radians(a)?.cos()?,
+        ("tan", [a]) => radians(a)?.tan()?,
+        ("asin", [a]) => decimal_convert_radians(a.asin()?, angle_mode)?,
+        ("acos", [a]) => decimal_convert_radians(a.acos()?, angle_mode)?,
+        ("atan", [a]) => decimal_convert_radians(a.atan(), angle_mode)?,
+        ("atan2", [y, x]) => decimal_convert_radians(y.atan2(x)?, angle_mode)?,
+        ("sec", [a]) => decimal_reciprocal("Secant", radians(a)?.cos()?)?,
+        ("csc", [a]) => decimal_reciprocal("Cosecant", radians(a)?.sin()?)?,
+        ("cot", [a]) => {
+            let angle = radians(a)?;
+            let sin = angle.sin()?;
+            if sin.is_zero() {
+                return Err("Cotangent is undefined at this angle!".to_string());
+            }
+            angle.cos()?.div(&sin)?
+        }
+        ("sinh", [a]) => a.sinh()?,
+        ("cosh", [a]) => a.cosh()?,
+        ("tanh", [a]) => a.tanh()?,
+        ("asinh", [a]) => a.asinh()?,
+        ("acosh", [a]) => a.acosh()?,
+        ("atanh", [a]) => a.atanh()?,
+        ("log", [a]) => a.log10()?,
+        ("ln", [a]) => a.ln()?,
+        ("abs", [a]) => a.abs(),
+        ("sign", [a]) => Decimal::from_i64(
+            if a.is_zero() {
+                0
+            } else if a.is_negative() {
+                -1
+            } else {
+                1
+            },
+            digits,
+        ),
+        ("gamma", [a]) => a.gamma()?,
+        ("lgamma", [a]) => a.lgamma()?,
+        ("fact", [a]) => a.add(&Decimal::from_i64(1, digits)).gamma()?,
+        ("digamma", [_]) => {
+            return Err("'digamma' is only available in floating point (precision off)".to_string())
+        }
+        (name, _) if is_builtin(name) => {
+            return Err(format!(
+                "Wrong number of arguments for '{}' (got {})",
+                name,
+                args.len()
+            ))
+        }
+        (name, _) => return Err(format!("Unknown function '{}'", name)),
+    };
+    Ok(Value::Decimal(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Statement;
+    use crate::parser::parse;
+
+    fn evaluate(input: &str, calc: &Calculator) -> Result<Value, String> {
+        match parse(input)? {
+            Statement::Expr(expr) => calculate(&expr, calc),
+            _ => panic!("'{}' is not an expression", input),
+        }
+    }
+
+    fn frac() -> Calculator {
+        let mut calc = Calculator::new();
+        calc.set_mode("frac").unwrap();
+        calc
+    }
+
+    fn ratio(numer: i64, denom: i64) -> BigRational {
+        BigRational::new(numer.into(), denom.into())
+    }
+
+    /// Runs `name = expr` the way the REPL does, recording the result in the history.
+    fn assign(input: &str, calc: &mut Calculator) -> Result<(), String> {
+        let Statement::Assign(name, expr) = parse(input)? else {
+            panic!("'{}' is not an assignment", input);
+        };
+        let value = calculate(&expr, calc)?;
+        calc.set_variable(&name, value.clone())?;
+        calc.add_to_history(&name, value);
+        Ok(())
+    }
+
+    #[test]
+    fn assigns_and_unsets_variables() {
+        let mut calc = Calculator::new();
+        assign("x = 3 + 4", &mut calc).unwrap();
+        assign("y = x^2 - 1", &mut calc).unwrap();
+        assert_eq!(evaluate("y / x", &calc), Ok(Value::Real(48.0 / 7.0)));
+        assign("x = 1", &mut calc).unwrap();
+        assert_eq!(evaluate("y", &calc), Ok(Value::Real(48.0)));
+        assert_eq!(
+            assign("sin = 1", &mut calc),
+            Err("Cannot assign to built-in function 'sin'".to_string())
+        );
+        calc.unset("x").unwrap();
+        assert_eq!(
+            evaluate("x + 1", &calc),
+            Err("Unknown variable 'x'".to_string())
+        );
+        assert!(calc.unset("x").is_err());
+    }
+
+    #[test]
+    fn reads_previous_results_from_the_history() {
+        let mut calc = Calculator::new();
+        assert_eq!(
+            evaluate("ans", &calc),
+            Err("No previous result".to_string())
+        );
+        let first = evaluate("2 + 3", &calc).unwrap();
+        calc.add_to_history("2 + 3", first);
+        let second = evaluate("ans * 2", &calc).unwrap();
+        calc.add_to_history("ans * 2", second);
+        assert_eq!(evaluate("_", &calc), Ok(Value::Real(10.0)));
+        assert_eq!(evaluate("ans1 + ans2", &calc), Ok(Value::Real(15.0)));
+        assign("x = ans1", &mut calc).unwrap();
+        assert_eq!(evaluate("ans3", &calc), Ok(Value::Real(5.0)));
+        assert_eq!(
+            evaluate("ans4", &calc),
+            Err("No history entry 4".to_string())
+        );
+        assert!(evaluate("ans0", &calc).is_err());
+        assert_eq!(
+            assign("ans2 = 1", &mut calc),
+            Err("'ans2' is reserved for previous results".to_string())
+        );
+    }
+
+    fn define(input: &str, calc: &mut Calculator) {
+        let Ok(Statement::Define(name, params, body)) = parse(input) else {
+            panic!("'{}' is not a definition", input);
+        };
+        calc.define_function(&name, params, body).unwrap();
+    }
+
+    #[test]
+    fn limits_the_depth_of_user_function_calls() {
+        let mut calc = Calculator::new();
+        define("f(x) = 2x + 1", &mut calc);
+        define("g(x) = f(x)^2", &mut calc);
+        assert_eq!(evaluate("g(1)", &calc), Ok(Value::Real(9.0)));
+        assert_eq!(
+            evaluate("f(1, 2)", &calc),
+            Err("Function 'f' expects 1 argument(s), got 2".to_string())
+        );
+        define("r(x) = r(x) + 1", &mut calc);
+        assert_eq!(
+            evaluate("r(1)", &calc),
+            Err(format!(
+                "Maximum recursion depth ({}) exceeded in 'r'",
+                MAX_CALL_DEPTH
+            ))
+        );
+        define("a(x) = b(x)", &mut calc);
+        define("b(x) = a(x)", &mut calc);
+        assert!(evaluate("a(1)", &calc)
+            .unwrap_err()
+            .starts_with("Maximum recursion depth"));
+    }
+
+    #[test]
+    fn converts_angles_in_every_angle_mode() {
+        let mut calc = Calculator::new();
+        let close = |input: &str, calc: &Calculator, expected: f64| {
+            let actual = evaluate(input, calc).unwrap().to_f64();
+            assert!((actual - expected).abs() < 1e-14, "{} = {}", input, actual);
+        };
+        close("sin 90", &calc, 1.0);
+        close("cos 60", &calc, 0.5);
+        close("asin 1", &calc, 90.0);
+        close("atan 1", &calc, 45.0);
+        calc.set_mode("rad").unwrap();
+        close("sin(pi / 2)", &calc, 1.0);
+        close("sin 90", &calc, 90f64.sin());
+        close("asin 1", &calc, std::f64::consts::FRAC_PI_2);
+        calc.set_mode("grad").unwrap();
+        close("sin 100", &calc, 1.0);
+        close("cos 200", &calc, -1.0);
+        close("acos 0", &calc, 100.0);
+    }
+
+    #[test]
+    fn rejects_inverse_hyperbolics_outside_their_domain() {
+        let acosh_error = "Cannot calculate inverse hyperbolic cosine of value below 1!";
+        let atanh_error = "Inverse hyperbolic tangent only defined for values in (-1, 1)!";
+        let mut calc = Calculator::new();
+        for precision in ["off", "30"] {
+            calc.set_precision(precision).unwrap();
+            assert_eq!(evaluate("acosh 0.5", &calc), Err(acosh_error.to_string()));
+            assert_eq!(evaluate("atanh 1", &calc), Err(atanh_error.to_string()));
+            assert_eq!(evaluate("atanh(-1)", &calc), Err(atanh_error.to_string()));
+            assert_eq!(evaluate("atanh 2", &calc), Err(atanh_error.to_string()));
+            assert_eq!(evaluate("acosh 1", &calc).unwrap().to_f64(), 0.0);
+            let atanh = evaluate("atanh 0.5", &calc).unwrap().to_f64();
+            assert!((atanh - 0.5493061443340549).abs() < 1e-15, "{}", atanh);
+        }
+        calc.set_mode("complex").unwrap();
+        let acosh = evaluate("acosh 0.5", &calc).unwrap();
+        assert_eq!(acosh.to_string(), "1.0471975511965979i");
+    }
+
+    #[test]
+    fn computes_cotangent_as_cosine_over_sine() {
+        let calc = Calculator::new();
+        assert_eq!(evaluate("cot 90", &calc), Ok(Value::Real(0.0)));
+        assert_eq!(evaluate("cot 270", &calc), Ok(Value::Real(0.0)));
+        let cot_45 = evaluate("cot 45", &calc).unwrap().to_f64();
+        assert!((cot_45 - 1.0).abs() < 1e-15);
+        assert!(evaluate("cot 180", &calc).is_err());
+    }
+
+    #[test]
+    fn reads_h_as_planck_and_hr_as_the_hour() {
+        let calc = Calculator::new();
+        let hours = evaluate("2 hr to s", &calc).unwrap();
+        assert_eq!(hours.to_string(), "7200 s");
+        let planck = evaluate("h", &calc).unwrap().to_f64();
+        assert_eq!(planck, 6.62607015e-34);
+    }
+
+    #[test]
+    fn raises_rationals_to_rational_powers_exactly() {
+        assert_eq!(
+            rational_power(&ratio(4, 9), &ratio(3, 2)),
+            Ok(Some(ratio(8, 27)))
+        );
+        assert_eq!(
+            rational_power(&ratio(-8, 1), &ratio(1, 3)),
+            Ok(Some(ratio(-2, 1)))
+        );
+        assert_eq!(
+            rational_power(&ratio(2, 3), &ratio(-2, 1)),
+            Ok(Some(ratio(9, 4)))
+        );
+    }
+
+    #[test]
+    fn leaves_irrational_powers_to_floating_point() {
+        assert_eq!(rational_power(&ratio(2, 1), &ratio(1, 2)), Ok(None));
+        assert_eq!(rational_power(&ratio(-4, 1), &ratio(1, 2)), Ok(None));
+        assert_eq!(rational_power(&ratio(3, 1), &ratio(1 << 30, 1)), Ok(None));
+        assert!(rational_power(&ratio(0, 1), &ratio(-1, 1)).is_err());
+    }
+
+    #[test]
+    fn reads_fraction_literals_from_their_digits() {
+        let calc = frac();
+        assert_eq!(evaluate("0.125", &calc), Ok(Value::Rational(ratio(1, 8))));
+        assert_eq!(
+            evaluate("0.1 + 0.2", &calc),
+            Ok(Value::Rational(ratio(3, 10)))
+        );
+        let big: BigInt = "12345678901234567891".parse().unwrap();
+        assert_eq!(
+            evaluate("12345678901234567891", &calc),
+            Ok(Value::Rational(BigRational::from_integer(big)))
+        );
+    }
+}
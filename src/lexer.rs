@@ -0,0 +1,266 @@
+use num_bigint::BigInt;
+use num_traits::Num;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    /// A decimal literal's digits, without separators, e.g. `6.022e23`.
+    Number(String),
+    /// A hexadecimal, octal or binary literal, kept exact.
+    Integer(BigInt),
+    Ident(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    Ampersand,
+    Pipe,
+    Tilde,
+    ShiftLeft,
+    ShiftRight,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    Equals,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "{}", value),
+            Token::Integer(n) => write!(f, "{}", n),
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Star => write!(f, "*"),
+            Token::Slash => write!(f, "/"),
+            Token::Caret => write!(f, "^"),
+            Token::Ampersand => write!(f, "&"),
+            Token::Pipe => write!(f, "|"),
+            Token::Tilde => write!(f, "~"),
+            Token::ShiftLeft => write!(f, "<<"),
+            Token::ShiftRight => write!(f, ">>"),
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+            Token::LBracket => write!(f, "["),
+            Token::RBracket => write!(f, "]"),
+            Token::Comma => write!(f, ","),
+            Token::Equals => write!(f, "="),
+        }
+    }
+}
+
+pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+
+        if c.is_whitespace() {
+            pos += 1;
+        } else if let Some(radix) = radix_prefix(&chars, pos) {
+            let (value, end) = lex_radix(&chars, pos + 2, radix)?;
+            tokens.push(Token::Integer(value));
+            pos = end;
+        } else if c.is_ascii_digit() || c == '.' {
+            let (value, end) = lex_number(&chars, pos)?;
+            tokens.push(Token::Number(value));
+            pos = end;
+        } else if c.is_alphabetic() || c == '_' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            tokens.push(Token::Ident(chars[start..pos].iter().collect()));
+        } else if matches!((c, chars.get(pos + 1)), ('<', Some('<')) | ('>', Some('>'))) {
+            tokens.push(if c == '<' {
+                Token::ShiftLeft
+            } else {
+                Token::ShiftRight
+            });
+            pos += 2;
+        } else {
+            let token = match c {
+                '+' => Token::Plus,
+                '-' => Token::Minus,
+                '*' => Token::Star,
+                '/' => Token::Slash,
+                '^' => Token::Caret,
+                '&' => Token::Ampersand,
+                '|' => Token::Pipe,
+                '~' => Token::Tilde,
+                '(' => Token::LParen,
+                ')' => Token::RParen,
+                '[' => Token::LBracket,
+                ']' => Token::RBracket,
+                ',' => Token::Comma,
+                '=' => Token::Equals,
+                _ => return Err(format!("Unexpected character '{}'", c)),
+            };
+            tokens.push(token);
+            pos += 1;
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// The radix of a `0x`, `0o` or `0b` literal starting at `pos`.
+fn radix_prefix(chars: &[char], pos: usize) -> Option<u32> {
+    if chars[pos] != '0' {
+        return None;
+    }
+    match chars.get(pos + 1) {
+        Some('x') | Some('X') => Some(16),
+        Some('o') | Some('O') => Some(8),
+        Some('b') | Some('B') => Some(2),
+        _ => None,
+    }
+}
+
+/// Lexes the digits of a hexadecimal, octal or binary literal, which may also use `_`
+/// separators: `0xFF`, `0o17`, `0b1010_0101`.
+fn lex_radix(chars: &[char], start: usize, radix: u32) -> Result<(BigInt, usize), String> {
+    let mut pos = start;
+    let mut text = String::new();
+    while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '_') {
+        if chars[pos] == '_' {
+            let next_is_digit = chars.get(pos + 1).is_some_and(|c| c.is_digit(radix));
+            if text.is_empty() || !next_is_digit {
+                return Err("Digit separator '_' must sit between digits".to_string());
+            }
+        } else {
+            text.push(chars[pos]);
+        }
+        pos += 1;
+    }
+    let name = match radix {
+        16 => "hexadecimal",
+        8 => "octal",
+        _ => "binary",
+    };
+    let literal: String = chars[start - 2..pos].iter().collect();
+    if text.is_empty() {
+        return Err(format!("Invalid {} literal '{}'", name, literal));
+    }
+    let value = BigInt::from_str_radix(&text, radix)
+        .map_err(|_| format!("Invalid {} literal '{}'", name, literal))?;
+    Ok((value, pos))
+}
+
+/// Lexes a decimal literal starting at `start`: `42`, `.5`, `1_000_000`, `6.022e23`, `1E-3`.
+/// Returns the literal's digits and the position just past it; the digits are kept as text so
+/// precision and fraction modes can read them exactly.
+fn lex_number(chars: &[char], start: usize) -> Result<(String, usize), String> {
+    let mut pos = start;
+    let mut text = String::new();
+
+    let digits = |pos: &mut usize, text: &mut String| -> Result<usize, String> {
+        let mut count = 0;
+        while *pos < chars.len() && (chars[*pos].is_ascii_digit() || chars[*pos] == '_') {
+            if chars[*pos] == '_' {
+                let next_is_digit = chars.get(*pos + 1).is_some_and(|c| c.is_ascii_digit());
+                if count == 0 || !next_is_digit {
+                    return Err("Digit separator '_' must sit between digits".to_string());
+                }
+            } else {
+                text.push(chars[*pos]);
+                count += 1;
+            }
+            *pos += 1;
+        }
+        Ok(count)
+    };
+
+    let integer_digits = digits(&mut pos, &mut text)?;
+    let mut fraction_digits = 0;
+    if chars.get(pos) == Some(&'.') {
+        text.push('.');
+        pos += 1;
+        fraction_digits = digits(&mut pos, &mut text)?;
+    }
+    if integer_digits + fraction_digits == 0 {
+        return Err("Invalid number '.'".to_string());
+    }
+
+    // Only treat `e` as an exponent when digits follow, so `2e` still lexes as `2` then `e`.
+    if matches!(chars.get(pos), Some('e') | Some('E')) {
+        let mut lookahead = pos + 1;
+        if matches!(chars.get(lookahead), Some('+') | Some('-')) {
+            lookahead += 1;
+        }
+        if chars.get(lookahead).is_some_and(|c| c.is_ascii_digit()) {
+            text.push('e');
+            text.extend(&chars[pos + 1..lookahead]);
+            pos = lookahead;
+            digits(&mut pos, &mut text)?;
+        }
+    }
+
+    if chars.get(pos) == Some(&'.') {
+        return Err(format!("Invalid number '{}.'", text));
+    }
+
+    text.parse::<f64>()
+        .map_err(|_| format!("Invalid number '{}'", text))?;
+    Ok((text, pos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(digits: &str) -> Token {
+        Token::Number(digits.to_string())
+    }
+
+    #[test]
+    fn lexes_scientific_notation() {
+        assert_eq!(tokenize("6.022e23"), Ok(vec![number("6.022e23")]));
+        assert_eq!(tokenize("1E-3"), Ok(vec![number("1e-3")]));
+        assert_eq!(tokenize("2.5e+4"), Ok(vec![number("2.5e+4")]));
+        // Without exponent digits, `e` is the constant.
+        assert_eq!(
+            tokenize("2e"),
+            Ok(vec![number("2"), Token::Ident("e".to_string())])
+        );
+    }
+
+    #[test]
+    fn keeps_all_digits_of_long_literals() {
+        assert_eq!(
+            tokenize("1.00000000000000000001"),
+            Ok(vec![number("1.00000000000000000001")])
+        );
+    }
+
+    #[test]
+    fn lexes_leading_dots_and_separators() {
+        assert_eq!(tokenize(".5"), Ok(vec![number(".5")]));
+        assert_eq!(tokenize("1_000_000"), Ok(vec![number("1000000")]));
+        assert_eq!(
+            tokenize("0b1010_0101"),
+            Ok(vec![Token::Integer(BigInt::from(0b1010_0101))])
+        );
+        assert_eq!(
+            tokenize("0xff"),
+            Ok(vec![Token::Integer(BigInt::from(255))])
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_numbers() {
+        assert!(tokenize(".").is_err());
+        assert!(tokenize("1.2.3").is_err());
+        assert!(tokenize("1__000").is_err());
+        assert!(tokenize("1_").is_err());
+        assert!(tokenize("0x").is_err());
+        assert!(tokenize("0b12").is_err());
+        assert!(tokenize("2 $ 3").is_err());
+    }
+}
@@ -1,179 +1,129 @@
+mod ast;
+mod calculator;
+mod combinatorics;
+mod complex;
+mod constants;
+mod decimal;
+mod eval;
+mod lexer;
+mod matrix;
+mod numeric;
+mod parser;
+mod polynomial;
+mod programmer;
+mod solve;
+mod special;
+mod stats;
+mod symbolic;
+mod units;
+mod value;
+
+use ast::{Expr, Statement};
+use calculator::Calculator;
 use colored::*;
-use regex::Regex;
-use rustyline::{DefaultEditor, Editor};
-use std::f64::consts::{E, PI};
-use std::str::FromStr;
+use eval::calculate;
+use rustyline::DefaultEditor;
 
-struct Calculator {
-    memory: f64,
-    history: Vec<String>,
+fn parse_statement(input: &str) -> Result<Statement, String> {
+    parser::parse(input)
 }
 
-impl Calculator {
-    fn new() -> Self {
-        Self {
-            memory: 0.0,
-            history: Vec::new(),
+fn execute(calc: &mut Calculator, input: &str) -> Result<(), String> {
+    match parse_statement(input)? {
+        Statement::Expr(Expr::Call(name, args)) if name == "integrate" => {
+            let (result, error) = eval::integrate(&args, calc)?;
+            println!(
+                "{} {} (error ≈ {:.1e})",
+                calc.relation(&result).bright_green(),
+                calc.format_value(&result),
+                error
+            );
+            calc.add_to_history(input, result);
         }
-    }
-
-    fn store_in_memory(&mut self, value: f64) {
-        self.memory = value;
-        println!("{}", "Value stored in memory.".bright_green());
-    }
-
-    fn add_to_memory(&mut self, value: f64) {
-        self.memory += value;
-        println!("{}", "Value added to memory.".bright_green());
-    }
-
-    fn recall_memory(&self) -> f64 {
-        self.memory
-    }
-
-    fn clear_memory(&mut self) {
-        self.memory = 0.0;
-        println!("{}", "Memory cleared.".bright_green());
-    }
-
-    fn add_to_history(&mut self, expression: &str, result: f64) {
-        self.history.push(format!("{} = {}", expression, result));
-    }
-
-    fn show_history(&self) {
-        println!("\n{}", "Calculation History:".bright_blue());
-        if self.history.is_empty() {
-            println!("No calculations yet.");
-        } else {
-            for (i, entry) in self.history.iter().enumerate() {
-                println!("{}. {}", i + 1, entry);
-            }
+        Statement::Expr(Expr::Call(name, args)) if name == "diff" && args.len() == 2 => {
+            let derivative = symbolic::differentiate(&args, calc)?;
+            println!("{} {}", "=".bright_green(), derivative);
         }
-    }
-}
-
-#[derive(Debug)]
-enum Operation {
-    Add(f64, f64),
-    Subtract(f64, f64),
-    Multiply(f64, f64),
-    Divide(f64, f64),
-    Power(f64, f64),
-    SquareRoot(f64),
-    Sine(f64),
-    Cosine(f64),
-    Tangent(f64),
-    Logarithm(f64),
-    NaturalLog(f64),
-    Factorial(f64),
-    Absolute(f64),
-}
-
-fn calculate(op: Operation) -> Result<f64, String> {
-    match op {
-        Operation::Add(a, b) => Ok(a + b),
-        Operation::Subtract(a, b) => Ok(a - b),
-        Operation::Multiply(a, b) => Ok(a * b),
-        Operation::Divide(a, b) => {
-            if b == 0.0 {
-                Err("Division by zero!".to_string())
-            } else {
-                Ok(a / b)
-            }
+        Statement::Expr(expr) => {
+            let result = calculate(&expr, calc)?;
+            println!(
+                "{} {}",
+                calc.relation(&result).bright_green(),
+                calc.format_value(&result)
+            );
+            calc.add_to_history(input, result);
         }
-        Operation::Power(a, b) => Ok(a.powf(b)),
-        Operation::SquareRoot(a) => {
-            if a < 0.0 {
-                Err("Cannot calculate square root of negative number!".to_string())
-            } else {
-                Ok(a.sqrt())
-            }
+        Statement::Assign(name, expr) => {
+            let value = calculate(&expr, calc)?;
+            calc.set_variable(&name, value.clone())?;
+            println!(
+                "{} {} {}",
+                name,
+                calc.relation(&value).bright_green(),
+                calc.format_value(&value)
+            );
+            calc.add_to_history(&name, value);
         }
-        Operation::Sine(a) => Ok(a.to_radians().sin()),
-        Operation::Cosine(a) => Ok(a.to_radians().cos()),
-        Operation::Tangent(a) => Ok(a.to_radians().tan()),
-        Operation::Logarithm(a) => {
-            if a <= 0.0 {
-                Err("Cannot calculate logarithm of non-positive number!".to_string())
-            } else {
-                Ok(a.log10())
-            }
+        Statement::Define(name, params, body) => {
+            let signature = format!("{}({})", name, params.join(", "));
+            calc.define_function(&name, params, body)?;
+            println!(
+                "{}",
+                format!("Function {} defined.", signature).bright_green()
+            );
         }
-        Operation::NaturalLog(a) => {
-            if a <= 0.0 {
-                Err("Cannot calculate natural logarithm of non-positive number!".to_string())
-            } else {
-                Ok(a.ln())
-            }
-        }
-        Operation::Factorial(a) => {
-            if a < 0.0 || a.fract() != 0.0 {
-                Err("Factorial only defined for non-negative integers!".to_string())
-            } else {
-                let n = a as u64;
-                Ok((1..=n).fold(1.0, |acc, x| acc * x as f64))
-            }
-        }
-        Operation::Absolute(a) => Ok(a.abs()),
-    }
-}
-
-fn parse_expression(input: &str) -> Result<Operation, String> {
-    let input = input.to_lowercase();
-    
-    // Handle special constants
-    let input = input.replace("pi", &PI.to_string());
-    let input = input.replace("e", &E.to_string());
-
-    // Basic operations regex
-    let basic_op_regex = Regex::new(r"^(-?\d*\.?\d+)\s*([\+\-\*/\^])\s*(-?\d*\.?\d+)$").unwrap();
-    
-    // Function regex
-    let func_regex = Regex::new(r"^(sqrt|sin|cos|tan|log|ln|abs|fact)\s*\(?(-?\d*\.?\d+)\)?$").unwrap();
-
-    if let Some(caps) = basic_op_regex.captures(&input) {
-        let a = f64::from_str(&caps[1]).map_err(|_| "Invalid first number")?;
-        let b = f64::from_str(&caps[3]).map_err(|_| "Invalid second number")?;
-        
-        match &caps[2] {
-            "+" => Ok(Operation::Add(a, b)),
-            "-" => Ok(Operation::Subtract(a, b)),
-            "*" => Ok(Operation::Multiply(a, b)),
-            "/" => Ok(Operation::Divide(a, b)),
-            "^" => Ok(Operation::Power(a, b)),
-            _ => Err("Unknown operator".to_string()),
-        }
-    } else if let Some(caps) = func_regex.captures(&input) {
-        let num = f64::from_str(&caps[2]).map_err(|_| "Invalid number")?;
-        
-        match &caps[1] {
-            "sqrt" => Ok(Operation::SquareRoot(num)),
-            "sin" => Ok(Operation::Sine(num)),
-            "cos" => Ok(Operation::Cosine(num)),
-            "tan" => Ok(Operation::Tangent(num)),
-            "log" => Ok(Operation::Logarithm(num)),
-            "ln" => Ok(Operation::NaturalLog(num)),
-            "abs" => Ok(Operation::Absolute(num)),
-            "fact" => Ok(Operation::Factorial(num)),
-            _ => Err("Unknown function".to_string()),
-        }
-    } else {
-        Err("Invalid expression format".to_string())
     }
+    Ok(())
 }
 
 fn print_help() {
     println!("{}", "\nAvailable Operations:".bright_green());
-    println!("  • Basic: + - * / ^");
-    println!("  • Functions: sqrt, sin, cos, tan, log, ln, abs, fact");
-    println!("  • Constants: pi, e");
+    println!("  • Basic: + - * / ^ with parentheses, e.g. (1 + 2) ^ 2");
+    println!("  • Functions: sqrt, sin, cos, tan, log, ln, abs, sign, fact");
+    println!("  • Inverse & reciprocal trig: asin, acos, atan, atan2(y, x), sec, csc, cot");
+    println!("  • Hyperbolic: sinh, cosh, tanh, asinh, acosh, atanh");
+    println!("  • Exact integers: fact, dfact (n!!), ncr(n, r), npr(n, r), multinomial(k1, k2, ...)");
+    println!("  • Gamma: gamma, lgamma, digamma (fact also accepts non-integers via gamma(x + 1))");
+    println!("  • Statistics: mean, median, mode, var, stdev (sample), varp, stdevp (population),");
+    println!("    min, max, sum, percentile(list, p), quartiles - on [1, 2, 3] or plain arguments");
+    println!("  • Matrices: [[1, 2], [3, 4]] with + - * and ^n, transpose, det, inv, rank, trace, identity(n)");
+    println!("  • solve <equations> - Solve linear equations separated by ';' (names without a value are unknowns)");
+    println!("  • Polynomial roots: roots(a, b, c, ...) from coefficients (highest power first) or roots(expr, x)");
+    println!("  • Numeric roots: nsolve(expr, x, guess) by Newton's method or nsolve(expr, x, a, b) on a bracket");
+    println!("  • Integrals: integrate(expr, x, a, b), where a and b may be inf or -inf");
+    println!("  • Derivatives: diff(expr, x) simplifies symbolically, diff(expr, x, at) evaluates at a point");
+    println!("  • Bitwise (integers): & | xor ~ << >>, with literals 0xFF, 0o17, 0b1010");
+    println!("  • Complex: re, im, arg, conj (with mode complex: i, sqrt(-4), ln(-1), ...)");
+    println!("  • Units: SI units with prefixes (km, mA, kWh, ...) plus min, hr, day, in, ft, mi, lb, mph, atm, psi, ...");
+    println!("    written after a number, e.g. 9.81 m/s^2 * 3 kg");
+    println!("  • Unit conversion: <expression> to <unit>, e.g. 60 mph to km/hr");
+    println!("  • Constants: pi, tau, e, phi, c, G, h, hbar, k_B, N_A, e_charge, ... (see consts)");
+    println!("  • Previous results: ans or _ (latest), ans1, ans2, ... (history entries)");
     
     println!("\n{}", "Memory Commands:".bright_green());
     println!("  • ms <number> - Store in memory");
     println!("  • m+ <number> - Add to memory");
     println!("  • mr - Recall from memory");
     println!("  • mc - Clear memory");
+
+    println!("\n{}", "Variable & Function Commands:".bright_green());
+    println!("  • <name> = <expression> - Assign a variable");
+    println!("  • vars - List variables");
+    println!("  • <name>(<params>) = <expression> - Define a function, e.g. f(x, y) = x^2 + y");
+    println!("  • funcs - List user-defined functions");
+    println!("  • consts - List built-in constants (these cannot be reassigned)");
+    println!("  • unset <name> - Remove a variable or function");
     
+    println!("\n{}", "Mode Commands:".bright_green());
+    println!("  • mode deg|rad|grad - Set the angle unit for trig functions (default deg)");
+    println!("  • mode frac|float - Exact fractions for + - * / ^, or plain floating point");
+    println!("  • mode complex - Complex arithmetic with i; mode rect|polar picks the output form");
+    println!("  • mode prog - Integer literals; results shown in dec, hex, oct and bin");
+    println!("  • word i8|i16|i32|i64|i128|u8|...|u128|off - Fixed-width integers (enters mode prog)");
+    println!("  • overflow wrap|saturate|error - What fixed-width arithmetic does on overflow");
+    println!("  • precision <digits>|off - Evaluate with arbitrary-precision decimals");
+    println!("  • truncate <digits>|off - Shorten long exact integers when printing");
+
     println!("\n{}", "Other Commands:".bright_green());
     println!("  • help - Show this help message");
     println!("  • history - Show calculation history");
@@ -186,8 +136,27 @@ fn print_help() {
     println!("  • 3 * pi");
     println!("  • sqrt 16");
     println!("  • 2 ^ 3");
+    println!("  • 6.022e23 / 1_000");
     println!("  • fact 5");
+    println!("  • ncr(52, 5)");
+    println!("  • stdev([2, 4, 4, 4, 5, 5, 7, 9])");
+    println!("  • A = [[2, 1], [1, 3]], then inv(A) * [[5], [10]]");
+    println!("  • solve 2x + y = 5; x - y = 1");
+    println!("  • roots(x^3 - 1, x)");
+    println!("  • nsolve(x^3 - x - 1, x, 1, 2)");
+    println!("  • integrate(e^(-x^2), x, -inf, inf)");
+    println!("  • diff(sin(x) * x^2, x)");
+    println!("  • precision 50, then sqrt 2");
+    println!("  • mode frac, then 1/3 + 1/6");
+    println!("  • mode complex, then (3 + 4i) * i");
+    println!("  • mode prog, then 0xF0 | 0b1010 << 1");
+    println!("  • word i8, then 100 + 100");
+    println!("  • 9.81 m/s^2 * 3 kg");
+    println!("  • 60 mph to km/hr");
+    println!("  • m_e * c^2 to MeV");
     println!("  • abs -4.2");
+    println!("  • r = 2.5, then pi * r ^ 2");
+    println!("  • ans * 2");
     println!();
 }
 
@@ -199,7 +168,8 @@ fn main() {
     let mut rl = DefaultEditor::new().unwrap();
 
     loop {
-        match rl.readline("calc> ".bright_yellow().to_string().as_str()) {
+        let prompt = format!("calc[{}]> ", calc.prompt_label());
+        match rl.readline(prompt.bright_yellow().to_string().as_str()) {
             Ok(line) => {
                 rl.add_history_entry(line.as_str()).unwrap();
                 let input = line.trim();
@@ -214,30 +184,58 @@ fn main() {
                     "history" => calc.show_history(),
                     "mr" => println!("Memory: {}", calc.recall_memory()),
                     "mc" => calc.clear_memory(),
+                    "vars" => calc.show_variables(),
+                    "funcs" => calc.show_functions(),
+                    "consts" => calc.show_constants(),
+                    "mode" => println!("Mode: {}", calc.prompt_label()),
                     input => {
-                        if input.starts_with("ms ") {
-                            if let Ok(value) = input[3..].trim().parse::<f64>() {
+                        if let Some(rest) = input.strip_prefix("ms ") {
+                            if let Ok(value) = rest.trim().parse::<f64>() {
                                 calc.store_in_memory(value);
                             } else {
                                 println!("{} Invalid number format", "Error:".bright_red());
                             }
-                        } else if input.starts_with("m+ ") {
-                            if let Ok(value) = input[3..].trim().parse::<f64>() {
+                        } else if let Some(rest) = input.strip_prefix("m+ ") {
+                            if let Ok(value) = rest.trim().parse::<f64>() {
                                 calc.add_to_memory(value);
                             } else {
                                 println!("{} Invalid number format", "Error:".bright_red());
                             }
-                        } else {
-                            match parse_expression(input) {
-                                Ok(operation) => match calculate(operation) {
-                                    Ok(result) => {
-                                        println!("{} {}", "=".bright_green(), result);
-                                        calc.add_to_history(input, result);
-                                    }
-                                    Err(e) => println!("{} {}", "Error:".bright_red(), e),
-                                },
+                        } else if let Some(mode) = input
+                            .strip_prefix("mode ")
+                            .map(str::trim)
+                            .filter(|mode| calc.is_mode_setting(mode))
+                        {
+                            if let Err(e) = calc.set_mode(&mode.to_lowercase()) {
+                                println!("{} {}", "Error:".bright_red(), e);
+                            }
+                        } else if let Some(setting) = input.strip_prefix("precision ") {
+                            if let Err(e) = calc.set_precision(&setting.trim().to_lowercase()) {
+                                println!("{} {}", "Error:".bright_red(), e);
+                            }
+                        } else if let Some(setting) = input.strip_prefix("word ") {
+                            if let Err(e) = calc.set_word(&setting.trim().to_lowercase()) {
+                                println!("{} {}", "Error:".bright_red(), e);
+                            }
+                        } else if let Some(setting) = input.strip_prefix("overflow ") {
+                            if let Err(e) = calc.set_overflow(&setting.trim().to_lowercase()) {
+                                println!("{} {}", "Error:".bright_red(), e);
+                            }
+                        } else if let Some(setting) = input.strip_prefix("truncate ") {
+                            if let Err(e) = calc.set_max_digits(&setting.trim().to_lowercase()) {
+                                println!("{} {}", "Error:".bright_red(), e);
+                            }
+                        } else if let Some(system) = input.strip_prefix("solve ") {
+                            match solve::solve(system, &calc) {
+                                Ok(solution) => solution.print(),
                                 Err(e) => println!("{} {}", "Error:".bright_red(), e),
                             }
+                        } else if let Some(name) = input.strip_prefix("unset ") {
+                            if let Err(e) = calc.unset(name.trim()) {
+                                println!("{} {}", "Error:".bright_red(), e);
+                            }
+                        } else if let Err(e) = execute(&mut calc, input) {
+                            println!("{} {}", "Error:".bright_red(), e);
                         }
                     }
                 }
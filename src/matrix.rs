@@ -0,0 +1,490 @@
+use crate::ast::BinaryOp;
+use crate::value::{format_real, Value};
+use std::fmt;
+
+pub const FUNCTIONS: &[&str] = &["transpose", "det", "inv", "rank", "trace", "identity"];
+
+/// Largest size accepted by `identity`, and the largest exponent for repeated products.
+const MAX_SIZE: usize = 1000;
+
+/// Pivots and eliminated entries smaller than this, relative to the largest entry in their
+/// column, count as zero. Each column is judged on its own scale, so the identity that `inv`
+/// appends or the constants of `solve` don't make small coefficients look like rounding error.
+const PIVOT_EPSILON: f64 = 1e-10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    rows: usize,
+    cols: usize,
+    /// Entries in row-major order.
+    data: Vec<f64>,
+}
+
+impl Matrix {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Matrix {
+            rows,
+            cols,
+            data: vec![0.0; rows * cols],
+        }
+    }
+
+    /// Builds a matrix from the rows of a literal such as `[[1, 2], [3, 4]]`.
+    pub fn from_rows(rows: &[Vec<Value>]) -> Result<Self, String> {
+        let cols = rows.first().map_or(0, Vec::len);
+        if cols == 0 {
+            return Err("A matrix needs at least one column".to_string());
+        }
+        if rows.iter().any(|row| row.len() != cols) {
+            return Err("Matrix rows must all have the same length".to_string());
+        }
+        let data = rows
+            .iter()
+            .flatten()
+            .map(|entry| match entry {
+                Value::Real(_) | Value::Integer(_) | Value::Decimal(_) | Value::Rational(_) => {
+                    Ok(entry.to_f64())
+                }
+                Value::Complex(z) if z.im == 0.0 => Ok(z.re),
+                _ => Err("Matrix entries must be real numbers".to_string()),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Matrix {
+            rows: rows.len(),
+            cols,
+            data,
+        })
+    }
+
+    pub fn identity(n: usize) -> Self {
+        let mut m = Matrix::new(n, n);
+        for i in 0..n {
+            m.set(i, i, 1.0);
+        }
+        m
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        self.data[row * self.cols + col]
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, value: f64) {
+        self.data[row * self.cols + col] = value;
+    }
+
+    fn size(&self) -> String {
+        format!("{}×{}", self.rows, self.cols)
+    }
+
+    fn require_square(&self, operation: &str) -> Result<usize, String> {
+        if self.rows == self.cols {
+            Ok(self.rows)
+        } else {
+            Err(format!(
+                "{} needs a square matrix, got {}",
+                operation,
+                self.size()
+            ))
+        }
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut t = Matrix::new(self.cols, self.rows);
+        for r in 0..self.rows {
+            for c in 0..self.cols {
+                t.set(c, r, self.get(r, c));
+            }
+        }
+        t
+    }
+
+    pub fn scale(&self, k: f64) -> Self {
+        Matrix {
+            data: self.data.iter().map(|x| x * k).collect(),
+            ..self.clone()
+        }
+    }
+
+    fn elementwise(&self, other: &Matrix, op: BinaryOp) -> Result<Self, String> {
+        if (self.rows, self.cols) != (other.rows, other.cols) {
+            return Err(format!(
+                "Matrix dimensions differ: {} and {}",
+                self.size(),
+                other.size()
+            ));
+        }
+        let data = self
+            .data
+            .iter()
+            .zip(&other.data)
+            .map(|(a, b)| if op == BinaryOp::Add { a + b } else { a - b })
+            .collect();
+        Ok(Matrix {
+            data,
+            ..self.clone()
+        })
+    }
+
+    pub fn multiply(&self, other: &Matrix) -> Result<Self, String> {
+        if self.cols != other.rows {
+            return Err(format!(
+                "Cannot multiply {} by {} matrix: inner dimensions differ",
+                self.size(),
+                other.size()
+            ));
+        }
+        let mut product = Matrix::new(self.rows, other.cols);
+        for r in 0..self.rows {
+            for c in 0..other.cols {
+                let sum = (0..self.cols)
+                    .map(|k| self.get(r, k) * other.get(k, c))
+                    .sum();
+                product.set(r, c, sum);
+            }
+        }
+        Ok(product)
+    }
+
+    fn power(&self, exponent: f64) -> Result<Self, String> {
+        let n = self.require_square("Matrix power")?;
+        if exponent.fract() != 0.0 || exponent.abs() > MAX_SIZE as f64 {
+            return Err(format!(
+                "Matrix powers need an integer exponent from -{} to {}",
+                MAX_SIZE, MAX_SIZE
+            ));
+        }
+        let base = if exponent < 0.0 {
+            self.inverse()?
+        } else {
+            self.clone()
+        };
+        let mut result = Matrix::identity(n);
+        for _ in 0..exponent.abs() as usize {
+            result = result.multiply(&base)?;
+        }
+        Ok(result)
+    }
+
+    /// Brings the matrix to reduced row echelon form with partial pivoting. Returns the pivot
+    /// columns and, for square matrices, the determinant: the product of the pivots with a
+    /// sign flip per row swap.
+    pub fn row_reduce(&mut self) -> (Vec<usize>, f64) {
+        let tolerance: Vec<f64> = (0..self.cols)
+            .map(|c| {
+                let largest = (0..self.rows).fold(0.0_f64, |m, r| m.max(self.get(r, c).abs()));
+                PIVOT_EPSILON * largest
+            })
+            .collect();
+        let mut pivots = Vec::new();
+        let mut determinant = 1.0;
+        for col in 0..self.cols {
+            let row = pivots.len();
+            if row == self.rows {
+                break;
+            }
+            let best = (row..self.rows)
+                .max_by(|&a, &b| self.get(a, col).abs().total_cmp(&self.get(b, col).abs()))
+                .unwrap_or(row);
+            if self.get(best, col).abs() <= tolerance[col] {
+                for r in row..self.rows {
+                    self.set(r, col, 0.0);
+                }
+                determinant = 0.0;
+                continue;
+            }
+            if best != row {
+                for c in 0..self.cols {
+                    self.data.swap(row * self.cols + c, best * self.cols + c);
+                }
+                determinant = -determinant;
+            }
+            let pivot = self.get(row, col);
+            determinant *= pivot;
+            for c in 0..self.cols {
+                self.set(row, c, self.get(row, c) / pivot);
+            }
+            for r in 0..self.rows {
+                let factor = self.get(r, col);
+                if r == row || factor == 0.0 {
+                    continue;
+                }
+                for (c, &tolerance) in tolerance.iter().enumerate() {
+                    let value = self.get(r, c) - factor * self.get(row, c);
+                    self.set(r, c, if value.abs() <= tolerance { 0.0 } else { value });
+                }
+            }
+            pivots.push(col);
+        }
+        (pivots, determinant)
+    }
+
+    pub fn determinant(&self) -> Result<f64, String> {
+        self.require_square("det")?;
+        let (_, determinant) = self.clone().row_reduce();
+        Ok(determinant)
+    }
+
+    pub fn inverse(&self) -> Result<Self, String> {
+        let n = self.require_square("inv")?;
+        let mut augmented = Matrix::new(n, 2 * n);
+        for r in 0..n {
+            for c in 0..n {
+                augmented.set(r, c, self.get(r, c));
+            }
+            augmented.set(r, n + r, 1.0);
+        }
+        let (pivots, _) = augmented.row_reduce();
+        if pivots.len() < n || pivots[n - 1] >= n {
+            return Err("Matrix is singular and has no inverse".to_string());
+        }
+        let mut inverse = Matrix::new(n, n);
+        for r in 0..n {
+            for c in 0..n {
+                inverse.set(r, c, augmented.get(r, n + c));
+            }
+        }
+        Ok(inverse)
+    }
+
+    pub fn rank(&self) -> usize {
+        self.clone().row_reduce().0.len()
+    }
+
+    pub fn trace(&self) -> Result<f64, String> {
+        let n = self.require_square("trace")?;
+        Ok((0..n).map(|i| self.get(i, i)).sum())
+    }
+}
+
+/// A scalar operand of matrix arithmetic.
+fn scalar(value: &Value) -> Result<f64, String> {
+    match value {
+        Value::Real(_) | Value::Integer(_) | Value::Decimal(_) | Value::Rational(_) => {
+            Ok(value.to_f64())
+        }
+        Value::Complex(z) if z.im == 0.0 => Ok(z.re),
+        _ => Err("Matrices can only be combined with matrices and real numbers".to_string()),
+    }
+}
+
+pub fn apply_binary(op: BinaryOp, a: &Value, b: &Value) -> Result<Value, String> {
+    let result = match (op, a, b) {
+        (BinaryOp::Add | BinaryOp::Subtract, Value::Matrix(x), Value::Matrix(y)) => {
+            x.elementwise(y, op)?
+        }
+        (BinaryOp::Add | BinaryOp::Subtract, _, _) => {
+            return Err(format!(
+                "Cannot {} a matrix and a scalar",
+                if op == BinaryOp::Add {
+                    "add"
+                } else {
+                    "subtract"
+                }
+            ))
+        }
+        (BinaryOp::Multiply, Value::Matrix(x), Value::Matrix(y)) => x.multiply(y)?,
+        (BinaryOp::Multiply, Value::Matrix(m), k) | (BinaryOp::Multiply, k, Value::Matrix(m)) => {
+            m.scale(scalar(k)?)
+        }
+        (BinaryOp::Multiply, _, _) => return Err("Expected a matrix operand".to_string()),
+        (BinaryOp::Divide, Value::Matrix(m), k) if !matches!(k, Value::Matrix(_)) => {
+            let k = scalar(k)?;
+            if k == 0.0 {
+                return Err("Division by zero!".to_string());
+            }
+            m.scale(1.0 / k)
+        }
+        (BinaryOp::Divide, _, _) => {
+            return Err("Matrices can only be divided by scalars; multiply by inv(M)".to_string())
+        }
+        (BinaryOp::Power, Value::Matrix(m), k) if !matches!(k, Value::Matrix(_)) => {
+            m.power(scalar(k)?)?
+        }
+        (BinaryOp::Power, _, _) => {
+            return Err("Only square matrices can be raised to integer powers".to_string())
+        }
+    };
+    Ok(Value::Matrix(result))
+}
+
+pub fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+    match (name, args) {
+        ("identity", [n]) => {
+            let n = n
+                .to_integer()
+                .and_then(|n| usize::try_from(n).ok())
+                .filter(|n| (1..=MAX_SIZE).contains(n))
+                .ok_or_else(|| format!("identity needs a size from 1 to {}", MAX_SIZE))?;
+            Ok(Value::Matrix(Matrix::identity(n)))
+        }
+        (_, [Value::Matrix(m)]) => match name {
+            "transpose" => Ok(Value::Matrix(m.transpose())),
+            "det" => m.determinant().map(Value::Real),
+            "inv" => m.inverse().map(Value::Matrix),
+            "rank" => Ok(Value::Integer(m.rank().into())),
+            "trace" => m.trace().map(Value::Real),
+            _ => Err(format!("'{}' does not accept matrices", name)),
+        },
+        (_, [_]) => Err(format!("'{}' needs a matrix argument", name)),
+        _ => Err(format!(
+            "Wrong number of arguments for '{}' (got {})",
+            name,
+            args.len()
+        )),
+    }
+}
+
+/// Entries are shown to this many significant digits, so rounding error from elimination
+/// doesn't turn `inv([[1, 2], [3, 4]])` into a grid of `1.4999999999999998`.
+const DISPLAY_DIGITS: usize = 12;
+
+/// An entry as shown in the grid; entries negligible next to the largest one show as 0.
+pub fn format_entry(x: f64, largest: f64) -> String {
+    if x.abs() <= largest * 10f64.powi(-(DISPLAY_DIGITS as i32)) {
+        return "0".to_string();
+    }
+    let rounded: f64 = format!("{:.*e}", DISPLAY_DIGITS - 1, x)
+        .parse()
+        .unwrap_or(x);
+    format_real(rounded)
+}
+
+/// An aligned grid with one bracketed row per line.
+impl fmt::Display for Matrix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let largest = self.data.iter().fold(0.0_f64, |m, x| m.max(x.abs()));
+        let cells: Vec<String> = self
+            .data
+            .iter()
+            .map(|&x| format_entry(x, largest))
+            .collect();
+        let widths: Vec<usize> = (0..self.cols)
+            .map(|c| {
+                (0..self.rows)
+                    .map(|r| cells[r * self.cols + c].chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        for r in 0..self.rows {
+            if r > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "[")?;
+            for c in 0..self.cols {
+                write!(
+                    f,
+                    " {:>width$}",
+                    cells[r * self.cols + c],
+                    width = widths[c]
+                )?;
+            }
+            write!(f, " ]")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix(rows: &[&[f64]]) -> Matrix {
+        let rows: Vec<Vec<Value>> = rows
+            .iter()
+            .map(|row| row.iter().map(|&x| Value::Real(x)).collect())
+            .collect();
+        Matrix::from_rows(&rows).unwrap()
+    }
+
+    fn assert_close(actual: &Matrix, expected: &Matrix) {
+        assert_eq!((actual.rows, actual.cols), (expected.rows, expected.cols));
+        for (a, b) in actual.data.iter().zip(&expected.data) {
+            assert!(
+                (a - b).abs() <= 1e-12 * b.abs().max(1.0),
+                "{}\n{}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn computes_determinants() {
+        let det = matrix(&[&[1.0, 2.0], &[3.0, 4.0]]).determinant().unwrap();
+        assert!((det + 2.0).abs() < 1e-12, "{}", det);
+        let det = matrix(&[&[2.0, 0.0, 1.0], &[1.0, 3.0, 2.0], &[1.0, 1.0, 2.0]])
+            .determinant()
+            .unwrap();
+        assert!((det - 6.0).abs() < 1e-12, "{}", det);
+        assert_eq!(matrix(&[&[1.0, 2.0], &[2.0, 4.0]]).determinant(), Ok(0.0));
+        assert!(matrix(&[&[1.0, 2.0]]).determinant().is_err());
+    }
+
+    #[test]
+    fn inverts_regular_matrices() {
+        let inverse = matrix(&[&[4.0, 7.0], &[2.0, 6.0]]).inverse().unwrap();
+        assert_close(&inverse, &matrix(&[&[0.6, -0.7], &[-0.2, 0.4]]));
+        assert!(matrix(&[&[1.0, 2.0], &[2.0, 4.0]]).inverse().is_err());
+    }
+
+    #[test]
+    fn computes_rank_and_trace() {
+        assert_eq!(matrix(&[&[1.0, 2.0], &[2.0, 4.0]]).rank(), 1);
+        assert_eq!(matrix(&[&[0.0, 0.0], &[0.0, 0.0]]).rank(), 0);
+        assert_eq!(matrix(&[&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]]).rank(), 2);
+        assert_eq!(matrix(&[&[1.0, 2.0], &[3.0, 4.0]]).trace(), Ok(5.0));
+        assert!(matrix(&[&[1.0, 2.0]]).trace().is_err());
+    }
+
+    #[test]
+    fn builds_identities_and_transposes() {
+        assert_eq!(
+            call("identity", &[Value::Real(2.0)]),
+            Ok(Value::Matrix(matrix(&[&[1.0, 0.0], &[0.0, 1.0]])))
+        );
+        assert!(call("identity", &[Value::Real(0.0)]).is_err());
+        assert_eq!(
+            matrix(&[&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]]).transpose(),
+            matrix(&[&[1.0, 4.0], &[2.0, 5.0], &[3.0, 6.0]])
+        );
+    }
+
+    #[test]
+    fn rejects_mismatched_dimensions() {
+        let square = Value::Matrix(matrix(&[&[1.0, 2.0], &[3.0, 4.0]]));
+        let wide = Value::Matrix(matrix(&[&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]]));
+        assert!(apply_binary(BinaryOp::Add, &square, &wide).is_err());
+        assert!(apply_binary(BinaryOp::Multiply, &wide, &wide).is_err());
+        assert!(apply_binary(BinaryOp::Power, &wide, &Value::Real(2.0)).is_err());
+        assert!(apply_binary(BinaryOp::Multiply, &square, &wide).is_ok());
+    }
+
+    #[test]
+    fn raises_matrices_to_integer_powers() {
+        let fibonacci = matrix(&[&[1.0, 1.0], &[1.0, 0.0]]);
+        assert_eq!(
+            fibonacci.power(10.0),
+            Ok(matrix(&[&[89.0, 55.0], &[55.0, 34.0]]))
+        );
+        assert_close(
+            &fibonacci.power(-1.0).unwrap(),
+            &matrix(&[&[0.0, 1.0], &[1.0, -1.0]]),
+        );
+        assert!(fibonacci.power(0.5).is_err());
+    }
+
+    #[test]
+    fn judges_pivots_relative_to_the_matrix_scale() {
+        let small = matrix(&[&[1e-11, 0.0], &[0.0, 1e-11]]);
+        assert_eq!(small.rank(), 2);
+        let det = small.determinant().unwrap();
+        assert!((det - 1e-22).abs() < 1e-34, "{}", det);
+        assert_close(
+            &small.inverse().unwrap(),
+            &matrix(&[&[1e11, 0.0], &[0.0, 1e11]]),
+        );
+        let singular = matrix(&[&[1e-11, 2e-11], &[2e-11, 4e-11]]);
+        assert_eq!(singular.rank(), 1);
+    }
+}
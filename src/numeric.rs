@@ -0,0 +1,457 @@
+use crate::value::format_real;
+
+pub const FUNCTIONS: &[&str] = &["nsolve", "integrate"];
+
+/// Iteration limits for Newton's and Brent's methods.
+const MAX_NEWTON_ITERATIONS: usize = 100;
+const MAX_BRENT_ITERATIONS: usize = 200;
+
+/// Steps smaller than this fraction of |x| mean the iteration has converged.
+const STEP_EPSILON: f64 = 1e-12;
+
+/// `integrate` stops once its error estimate is below both tolerances' larger value.
+const INTEGRAL_RELATIVE_TOLERANCE: f64 = 1e-10;
+const INTEGRAL_ABSOLUTE_TOLERANCE: f64 = 1e-12;
+
+const DIVERGES: &str = "integrate: the integral appears to diverge";
+
+/// Most subintervals adaptive quadrature will split the range into.
+const MAX_SUBINTERVALS: usize = 2000;
+
+/// Nodes of the 15-point Kronrod rule on [-1, 1] (the positive half, from the outside in);
+/// the odd-indexed ones are also the nodes of the 7-point Gauss rule.
+const KRONROD_NODES: [f64; 8] = [
+    0.9914553711208126,
+    0.9491079123427585,
+    0.8648644233597691,
+    0.7415311855993945,
+    0.5860872354676911,
+    0.4058451513773972,
+    0.20778495500789848,
+    0.0,
+];
+const KRONROD_WEIGHTS: [f64; 8] = [
+    0.022935322010529224,
+    0.06309209262997856,
+    0.10479001032225019,
+    0.14065325971552592,
+    0.1690047266392679,
+    0.19035057806478542,
+    0.20443294007529889,
+    0.20948214108472782,
+];
+const GAUSS_WEIGHTS: [f64; 4] = [
+    0.1294849661688697,
+    0.27970539148927664,
+    0.3818300505051189,
+    0.4179591836734694,
+];
+
+/// A real function of one variable, built by binding the variable of an expression.
+pub type Function<'a> = dyn Fn(f64) -> Result<f64, String> + 'a;
+
+/// A zero of `f` near `guess` by Newton's method, with the derivative taken by central
+/// differences.
+pub fn newton(f: &Function, guess: f64) -> Result<f64, String> {
+    let mut x = guess;
+    for _ in 0..MAX_NEWTON_ITERATIONS {
+        let fx = f(x)?;
+        if fx == 0.0 {
+            return Ok(x);
+        }
+        if !fx.is_finite() {
+            return Err(format!(
+                "nsolve: the expression is undefined at x = {}",
+                format_real(x)
+            ));
+        }
+        let h = 1e-7 * x.abs().max(1.0);
+        let slope = (f(x + h)? - f(x - h)?) / (2.0 * h);
+        if slope == 0.0 || !slope.is_finite() {
+            return Err(format!(
+                "nsolve: the derivative vanishes at x = {}; try another guess",
+                format_real(x)
+            ));
+        }
+        let step = fx / slope;
+        x -= step;
+        if step.abs() <= STEP_EPSILON * x.abs().max(1.0) {
+            return Ok(x);
+        }
+    }
+    Err(format!(
+        "nsolve did not converge after {} Newton iterations (last x = {})",
+        MAX_NEWTON_ITERATIONS,
+        format_real(x)
+    ))
+}
+
+/// A zero of `f` in `[a, b]` by Brent's method, which combines bisection's guarantee with the
+/// speed of secant and inverse quadratic steps. `f(a)` and `f(b)` must differ in sign.
+pub fn brent(f: &Function, mut a: f64, mut b: f64) -> Result<f64, String> {
+    let mut fa = f(a)?;
+    let mut fb = f(b)?;
+    if fa == 0.0 {
+        return Ok(a);
+    }
+    if fb == 0.0 {
+        return Ok(b);
+    }
+    if !fa.is_finite() || !fb.is_finite() || fa.signum() == fb.signum() {
+        return Err(format!(
+            "nsolve: f({}) and f({}) must have opposite signs to bracket a root",
+            format_real(a),
+            format_real(b)
+        ));
+    }
+
+    let (mut c, mut fc) = (a, fa);
+    let mut d = b - a;
+    let mut e = d;
+    for _ in 0..MAX_BRENT_ITERATIONS {
+        if fb.signum() == fc.signum() {
+            c = a;
+            fc = fa;
+            d = b - a;
+            e = d;
+        }
+        if fc.abs() < fb.abs() {
+            (a, b, c) = (b, c, b);
+            (fa, fb, fc) = (fb, fc, fb);
+        }
+
+        let tolerance = 2.0 * f64::EPSILON * b.abs() + 0.5 * f64::MIN_POSITIVE;
+        let midpoint = 0.5 * (c - b);
+        if midpoint.abs() <= tolerance || fb == 0.0 {
+            return Ok(b);
+        }
+
+        if e.abs() >= tolerance && fa.abs() > fb.abs() {
+            // Secant step when only two points are distinct, inverse quadratic otherwise.
+            let s = fb / fa;
+            let (mut p, mut q) = if a == c {
+                (2.0 * midpoint * s, 1.0 - s)
+            } else {
+                let q = fa / fc;
+                let r = fb / fc;
+                (
+                    s * (2.0 * midpoint * q * (q - r) - (b - a) * (r - 1.0)),
+                    (q - 1.0) * (r - 1.0) * (s - 1.0),
+                )
+            };
+            if p > 0.0 {
+                q = -q;
+            }
+            p = p.abs();
+            if 2.0 * p < (3.0 * midpoint * q - (tolerance * q).abs()).min((e * q).abs()) {
+                e = d;
+                d = p / q;
+            } else {
+                d = midpoint;
+                e = d;
+            }
+        } else {
+            d = midpoint;
+            e = d;
+        }
+
+        a = b;
+        fa = fb;
+        b += if d.abs() > tolerance {
+            d
+        } else {
+            tolerance.copysign(midpoint)
+        };
+        fb = f(b)?;
+        if !fb.is_finite() {
+            return Err(format!(
+                "nsolve: the expression is undefined at x = {}",
+                format_real(b)
+            ));
+        }
+    }
+    Err(format!(
+        "nsolve did not converge after {} Brent iterations (last x = {})",
+        MAX_BRENT_ITERATIONS,
+        format_real(b)
+    ))
+}
+
+/// The integral of `f` from `a` to `b` and an estimate of its absolute error, by adaptive
+/// Gauss–Kronrod quadrature: the subinterval with the largest error is bisected until the
+/// total error is within tolerance. Infinite bounds are mapped onto a finite range first.
+pub fn integrate(f: &Function, a: f64, b: f64) -> Result<(f64, f64), String> {
+    if a.is_nan() || b.is_nan() {
+        return Err("integrate: bounds must be numbers".to_string());
+    }
+    if a == b {
+        return Ok((0.0, 0.0));
+    }
+    if a > b {
+        let (value, error) = integrate(f, b, a)?;
+        return Ok((-value, error));
+    }
+
+    let f = |x: f64| {
+        // Only reached when bisection has pushed an infinite range's endpoint into view.
+        if !x.is_finite() {
+            return Err(DIVERGES.to_string());
+        }
+        let y = f(x).map_err(|e| {
+            format!(
+                "integrate: the integrand fails at x = {} ({})",
+                format_real(x),
+                e
+            )
+        })?;
+        if y.is_nan() {
+            Err(format!(
+                "integrate: the integrand is undefined at x = {}",
+                format_real(x)
+            ))
+        } else if y.is_infinite() {
+            Err(DIVERGES.to_string())
+        } else {
+            Ok(y)
+        }
+    };
+    match (a.is_finite(), b.is_finite()) {
+        (true, true) => adaptive(&f, a, b),
+        // x = a + t / (1 - t) for t in [0, 1)
+        (true, false) => adaptive(
+            &|t| {
+                let u = 1.0 - t;
+                Ok(f(a + t / u)? / (u * u))
+            },
+            0.0,
+            1.0,
+        ),
+        // x = b - (1 - t) / t for t in (0, 1]
+        (false, true) => adaptive(&|t| Ok(f(b - (1.0 - t) / t)? / (t * t)), 0.0, 1.0),
+        // x = t / (1 - t^2) for t in (-1, 1)
+        (false, false) => adaptive(
+            &|t| {
+                let u = 1.0 - t * t;
+                Ok(f(t / u)? * (1.0 + t * t) / (u * u))
+            },
+            -1.0,
+            1.0,
+        ),
+    }
+}
+
+fn adaptive(f: &Function, a: f64, b: f64) -> Result<(f64, f64), String> {
+    let mut intervals = vec![estimate(f, a, b)?];
+    loop {
+        let value: f64 = intervals.iter().map(|interval| interval.value).sum();
+        let error: f64 = intervals.iter().map(|interval| interval.error).sum();
+        let tolerance = INTEGRAL_ABSOLUTE_TOLERANCE.max(INTEGRAL_RELATIVE_TOLERANCE * value.abs());
+        if error <= tolerance {
+            return Ok((value, error));
+        }
+        if let Some(failure) = intervals
+            .iter()
+            .find_map(|interval| interval.failure.as_ref())
+        {
+            if intervals.len() >= MAX_SUBINTERVALS {
+                return Err(failure.clone());
+            }
+        }
+        if intervals.len() >= MAX_SUBINTERVALS {
+            return Err(format!(
+                "integrate did not converge after {} subintervals (error estimate {:.1e})",
+                MAX_SUBINTERVALS, error
+            ));
+        }
+
+        let worst = (0..intervals.len())
+            .max_by(|&i, &j| intervals[i].error.total_cmp(&intervals[j].error))
+            .unwrap_or(0);
+        let Interval { a, b, failure, .. } = intervals.swap_remove(worst);
+        let middle = 0.5 * (a + b);
+        if middle <= a || middle >= b {
+            return Err(failure.unwrap_or_else(|| DIVERGES.to_string()));
+        }
+        let (left, right) = (estimate(f, a, middle)?, estimate(f, middle, b)?);
+        // A point where the integrand fails, like 0 for sin(x)/x, is a node only by
+        // coincidence and no longer one of the halves'; failing on both means a whole range.
+        if let (Some(failure), Some(_), Some(_)) = (failure, &left.failure, &right.failure) {
+            return Err(failure);
+        }
+        intervals.push(left);
+        intervals.push(right);
+    }
+}
+
+struct Interval {
+    a: f64,
+    b: f64,
+    value: f64,
+    error: f64,
+    /// Why the integrand could not be evaluated at one of the nodes, if it couldn't.
+    failure: Option<String>,
+}
+
+/// The Kronrod estimate over `[a, b]`, or an interval with an unbounded error, so that it is
+/// bisected first, when the integrand fails at one of the nodes.
+fn estimate(f: &Function, a: f64, b: f64) -> Result<Interval, String> {
+    match kronrod(f, a, b) {
+        Err(failure) if failure != DIVERGES => Ok(Interval {
+            a,
+            b,
+            value: 0.0,
+            error: f64::INFINITY,
+            failure: Some(failure),
+        }),
+        result => result,
+    }
+}
+
+/// The 15-point Kronrod estimate of the integral over `[a, b]`, with its error estimated
+/// from the difference to the embedded 7-point Gauss rule as QUADPACK does.
+fn kronrod(f: &Function, a: f64, b: f64) -> Result<Interval, String> {
+    let center = 0.5 * (a + b);
+    let half = 0.5 * (b - a);
+    // The integrand itself is finite; this only overflows when an infinite range was mapped
+    // onto a finite one and the integrand doesn't decay.
+    let sample = |x: f64| -> Result<f64, String> {
+        let y = f(x)?;
+        if y.is_finite() {
+            Ok(y)
+        } else {
+            Err(DIVERGES.to_string())
+        }
+    };
+
+    let mut values = [0.0; 15];
+    for (i, node) in KRONROD_NODES.iter().enumerate() {
+        values[i] = sample(center - half * node)?;
+        if i < 7 {
+            values[14 - i] = sample(center + half * node)?;
+        }
+    }
+    let weight = |i: usize| KRONROD_WEIGHTS[i.min(14 - i)];
+    let kronrod: f64 = (0..15).map(|i| weight(i) * values[i]).sum();
+    let gauss: f64 = (0..15)
+        .filter(|&i| i.min(14 - i) % 2 == 1)
+        .map(|i| GAUSS_WEIGHTS[i.min(14 - i) / 2] * values[i])
+        .sum();
+    let mean = 0.5 * kronrod;
+    let spread: f64 = (0..15).map(|i| weight(i) * (values[i] - mean).abs()).sum();
+    let magnitude: f64 = (0..15).map(|i| weight(i) * values[i].abs()).sum();
+
+    let mut error = ((kronrod - gauss) * half).abs();
+    let spread = spread * half.abs();
+    if spread != 0.0 && error != 0.0 {
+        error = spread * (200.0 * error / spread).powf(1.5).min(1.0);
+    }
+    error = error.max(50.0 * f64::EPSILON * magnitude * half.abs());
+    Ok(Interval {
+        a,
+        b,
+        value: kronrod * half,
+        error,
+        failure: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: f64, expected: f64, tolerance: f64) -> bool {
+        (actual - expected).abs() <= tolerance * expected.abs().max(1.0)
+    }
+
+    #[test]
+    fn finds_roots_by_newtons_method() {
+        let root = newton(&|x| Ok(x * x - 2.0), 1.0).unwrap();
+        assert!(close(root, 2f64.sqrt(), 1e-14), "{}", root);
+        let root = newton(&|x| Ok(x.cos() - x), 0.0).unwrap();
+        assert!(close(root, 0.7390851332151607, 1e-14), "{}", root);
+        assert!(newton(&|x| Ok(x * x + 1.0), 0.0).is_err());
+    }
+
+    #[test]
+    fn finds_bracketed_roots_by_brents_method() {
+        let root = brent(&|x| Ok(x * x * x - 2.0 * x - 5.0), 2.0, 3.0).unwrap();
+        assert!(close(root, 2.0945514815423265, 1e-14), "{}", root);
+        let root = brent(&|x| Ok(x.exp() - 10.0), 0.0, 100.0).unwrap();
+        assert!(close(root, 10f64.ln(), 1e-14), "{}", root);
+        assert_eq!(brent(&|x| Ok(x - 3.0), 3.0, 5.0), Ok(3.0));
+        assert!(brent(&|x| Ok(x * x + 1.0), -1.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn integrates_polynomials_exactly_on_one_interval() {
+        let interval = kronrod(&|x| Ok(x.powi(22)), 0.0, 1.0).unwrap();
+        assert!(
+            close(interval.value, 1.0 / 23.0, 1e-14),
+            "{}",
+            interval.value
+        );
+        let interval = kronrod(&|x| Ok(3.0 * x * x), -1.0, 2.0).unwrap();
+        assert!(close(interval.value, 9.0, 1e-15), "{}", interval.value);
+    }
+
+    #[test]
+    fn integrates_over_finite_ranges() {
+        let (value, error) = integrate(&|x| Ok(x.sin()), 0.0, std::f64::consts::PI).unwrap();
+        assert!(
+            close(value, 2.0, 1e-12) && error < 1e-10,
+            "{} ± {}",
+            value,
+            error
+        );
+        let (value, _) = integrate(&|x| Ok(x.sqrt()), 0.0, 1.0).unwrap();
+        assert!(close(value, 2.0 / 3.0, 1e-10), "{}", value);
+        let (value, _) = integrate(&|x| Ok(x), 1.0, 0.0).unwrap();
+        assert!(close(value, -0.5, 1e-15), "{}", value);
+        assert_eq!(integrate(&|x| Ok(x), 2.0, 2.0), Ok((0.0, 0.0)));
+    }
+
+    #[test]
+    fn integrates_over_infinite_ranges() {
+        let (value, _) = integrate(&|x| Ok(1.0 / (x * x)), 1.0, f64::INFINITY).unwrap();
+        assert!(close(value, 1.0, 1e-10), "{}", value);
+        let (value, _) = integrate(&|x| Ok(x.exp()), f64::NEG_INFINITY, 0.0).unwrap();
+        assert!(close(value, 1.0, 1e-10), "{}", value);
+        let gaussian = |x: f64| Ok((-x * x).exp());
+        let (value, _) = integrate(&gaussian, f64::NEG_INFINITY, f64::INFINITY).unwrap();
+        assert!(
+            close(value, std::f64::consts::PI.sqrt(), 1e-10),
+            "{}",
+            value
+        );
+    }
+
+    #[test]
+    fn reports_divergent_integrals() {
+        assert!(integrate(&|x| Ok(1.0 / x), 1.0, f64::INFINITY).is_err());
+        assert!(integrate(&|_| Ok(1.0), 0.0, f64::INFINITY).is_err());
+        assert!(integrate(&|x| Ok(x.ln()), -1.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn steps_around_points_where_the_integrand_fails() {
+        let sinc = |x: f64| {
+            if x == 0.0 {
+                Err("Division by zero!".to_string())
+            } else {
+                Ok(x.sin() / x)
+            }
+        };
+        let (value, _) = integrate(&sinc, -1.0, 1.0).unwrap();
+        assert!(close(value, 1.892166140734366, 1e-12), "{}", value);
+        let failure = integrate(&|x| Ok(x.sqrt()), -1.0, 1.0).unwrap_err();
+        assert!(failure.contains("undefined at x = -"), "{}", failure);
+        let failure = integrate(&|_| Err("Unknown variable 'y'".to_string()), 0.0, 1.0);
+        let failure = failure.unwrap_err();
+        assert!(
+            failure.starts_with("integrate: the integrand fails at x = 0.")
+                && failure.ends_with("(Unknown variable 'y')"),
+            "{}",
+            failure
+        );
+    }
+}
@@ -0,0 +1,446 @@
+use crate::ast::{BinaryOp, BitOp, Expr, Statement, UnaryOp, UNARY_PRECEDENCE};
+use crate::eval::is_builtin;
+use crate::lexer::{tokenize, Token};
+use crate::units;
+
+/// Words that continue an expression rather than starting an implicit product, as in `2 to
+/// km` or `6 xor 3`.
+const KEYWORDS: &[&str] = &["to", "xor"];
+
+#[derive(Clone, Copy)]
+enum Operator {
+    Arithmetic(BinaryOp),
+    Bitwise(BitOp),
+}
+
+impl Operator {
+    fn from_token(token: &Token) -> Option<Self> {
+        match token {
+            Token::Plus => Some(Operator::Arithmetic(BinaryOp::Add)),
+            Token::Minus => Some(Operator::Arithmetic(BinaryOp::Subtract)),
+            Token::Star => Some(Operator::Arithmetic(BinaryOp::Multiply)),
+            Token::Slash => Some(Operator::Arithmetic(BinaryOp::Divide)),
+            Token::Caret => Some(Operator::Arithmetic(BinaryOp::Power)),
+            Token::Ampersand => Some(Operator::Bitwise(BitOp::And)),
+            Token::Pipe => Some(Operator::Bitwise(BitOp::Or)),
+            Token::ShiftLeft => Some(Operator::Bitwise(BitOp::ShiftLeft)),
+            Token::ShiftRight => Some(Operator::Bitwise(BitOp::ShiftRight)),
+            Token::Ident(name) if name == "xor" => Some(Operator::Bitwise(BitOp::Xor)),
+            _ => None,
+        }
+    }
+
+    fn precedence(self) -> u8 {
+        match self {
+            Operator::Arithmetic(op) => op.precedence(),
+            Operator::Bitwise(op) => op.precedence(),
+        }
+    }
+
+    fn is_right_associative(self) -> bool {
+        matches!(self, Operator::Arithmetic(op) if op.is_right_associative())
+    }
+
+    fn build(self, lhs: Expr, rhs: Expr) -> Expr {
+        match self {
+            Operator::Arithmetic(op) => Expr::Binary(op, Box::new(lhs), Box::new(rhs)),
+            Operator::Bitwise(op) => Expr::Bitwise(op, Box::new(lhs), Box::new(rhs)),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(ref token) if *token == expected => Ok(()),
+            Some(token) => Err(format!("Expected '{}', found '{}'", expected, token)),
+            None => Err(format!("Expected '{}', found end of input", expected)),
+        }
+    }
+
+    /// Precedence climbing: parses operators binding at least as tightly as `min_precedence`.
+    fn parse_expr(&mut self, min_precedence: u8) -> Result<Expr, String> {
+        let mut lhs = self.parse_unary()?;
+
+        while let Some(op) = self.peek().and_then(Operator::from_token) {
+            let precedence = op.precedence();
+            if precedence < min_precedence {
+                break;
+            }
+            self.next();
+
+            let next_min = if op.is_right_associative() {
+                precedence
+            } else {
+                precedence + 1
+            };
+            let rhs = self.parse_expr(next_min)?;
+            lhs = op.build(lhs, rhs);
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some(Token::Minus) => {
+                self.next();
+                let operand = self.parse_expr(UNARY_PRECEDENCE)?;
+                Ok(Expr::Unary(UnaryOp::Negate, Box::new(operand)))
+            }
+            Some(Token::Plus) => {
+                self.next();
+                self.parse_expr(UNARY_PRECEDENCE)
+            }
+            Some(Token::Tilde) => {
+                self.next();
+                let operand = self.parse_expr(UNARY_PRECEDENCE)?;
+                Ok(Expr::Unary(UnaryOp::Not, Box::new(operand)))
+            }
+            _ => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number(value)) => {
+                // Implicit multiplication: `4i`, `2x^2`, `3(1 + 2)`, but not `2 to ...`.
+                let implicit = match self.peek() {
+                    Some(Token::Ident(name)) => !KEYWORDS.contains(&name.as_str()),
+                    Some(Token::LParen) => true,
+                    _ => false,
+                };
+                if implicit {
+                    // `5 min` is five minutes, not the `min` function applied to what follows.
+                    let rhs = if self.at_unit(0) {
+                        self.parse_unit()?
+                    } else {
+                        self.parse_expr(UNARY_PRECEDENCE)?
+                    };
+                    Ok(Expr::Binary(
+                        BinaryOp::Multiply,
+                        Box::new(Expr::Number(value)),
+                        Box::new(rhs),
+                    ))
+                } else {
+                    Ok(Expr::Number(value))
+                }
+            }
+            Some(Token::Integer(n)) => Ok(Expr::Integer(n)),
+            Some(Token::LParen) => {
+                let inner = self.parse_expr(0)?;
+                self.expect(Token::RParen)?;
+                Ok(inner)
+            }
+            Some(Token::LBracket) => Ok(Expr::List(self.parse_list(Token::RBracket)?)),
+            Some(Token::Ident(name)) => {
+                // Built-in function names are case-insensitive, so `SIN 45` still works.
+                let name = if is_builtin(&name.to_lowercase()) {
+                    name.to_lowercase()
+                } else {
+                    name
+                };
+                if self.peek() == Some(&Token::LParen) {
+                    self.next();
+                    let args = self.parse_list(Token::RParen)?;
+                    Ok(Expr::Call(name, args))
+                } else if is_builtin(&name) && self.at_operand() {
+                    // `sin 45` applies the function to the following operand.
+                    let argument = self.parse_expr(UNARY_PRECEDENCE)?;
+                    Ok(Expr::Call(name, vec![argument]))
+                } else {
+                    Ok(Expr::Ident(name))
+                }
+            }
+            Some(token) => Err(format!("Unexpected token '{}'", token)),
+            None => Err("Unexpected end of input".to_string()),
+        }
+    }
+
+    /// Whether the next token can begin an operand, so `sin 45` applies `sin` but a bare
+    /// name such as the unit in `7200 s to min` does not.
+    fn at_operand(&self) -> bool {
+        match self.peek() {
+            Some(Token::Ident(name)) => !KEYWORDS.contains(&name.as_str()),
+            Some(
+                Token::Number(_)
+                | Token::Integer(_)
+                | Token::LParen
+                | Token::LBracket
+                | Token::Minus
+                | Token::Plus
+                | Token::Tilde,
+            ) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the token `offset` places ahead names a unit.
+    fn at_unit(&self, offset: usize) -> bool {
+        matches!(self.tokens.get(self.pos + offset), Some(Token::Ident(name)) if units::lookup(name).is_some())
+    }
+
+    /// The unit after a number, such as `m/s^2` in `9.81 m/s^2`: unit names joined by `*` and
+    /// `/`, each optionally raised to a power. A `/` or `*` followed by anything but a unit
+    /// name ends it, so `6 m / 2 s` divides two quantities.
+    fn parse_unit(&mut self) -> Result<Expr, String> {
+        let mut unit = self.parse_unit_power()?;
+        while let Some(op @ (Token::Star | Token::Slash)) = self.peek() {
+            let op = match op {
+                Token::Star => BinaryOp::Multiply,
+                _ => BinaryOp::Divide,
+            };
+            if !self.at_unit(1) {
+                break;
+            }
+            self.next();
+            let rhs = self.parse_unit_power()?;
+            unit = Expr::Binary(op, Box::new(unit), Box::new(rhs));
+        }
+        Ok(unit)
+    }
+
+    fn parse_unit_power(&mut self) -> Result<Expr, String> {
+        let Some(Token::Ident(name)) = self.next() else {
+            return Err("Expected a unit".to_string());
+        };
+        let unit = Expr::Unit(name);
+        if self.peek() != Some(&Token::Caret) {
+            return Ok(unit);
+        }
+        self.next();
+        let exponent = self.parse_expr(BinaryOp::Power.precedence())?;
+        Ok(Expr::Binary(
+            BinaryOp::Power,
+            Box::new(unit),
+            Box::new(exponent),
+        ))
+    }
+
+    /// A full expression, optionally followed by `to <unit>`.
+    fn parse_conversion(&mut self) -> Result<Expr, String> {
+        let value = self.parse_expr(0)?;
+        if !matches!(self.peek(), Some(Token::Ident(keyword)) if keyword == "to") {
+            return Ok(value);
+        }
+        self.next();
+        let unit = self.parse_expr(0)?;
+        Ok(Expr::Convert(Box::new(value), Box::new(unit)))
+    }
+
+    /// A line is a function definition when it contains `=` after `name(`.
+    fn is_definition(&self) -> bool {
+        self.tokens.contains(&Token::Equals)
+    }
+
+    /// Parses `x, y)` after `f(` in a definition; parameters must be distinct plain names.
+    fn parse_parameters(&mut self) -> Result<Vec<String>, String> {
+        let mut params: Vec<String> = Vec::new();
+        if self.peek() == Some(&Token::RParen) {
+            self.next();
+            return Ok(params);
+        }
+
+        loop {
+            match self.next() {
+                Some(Token::Ident(param)) => {
+                    if params.contains(&param) {
+                        return Err(format!("Duplicate parameter '{}'", param));
+                    }
+                    params.push(param);
+                }
+                Some(token) => return Err(format!("Expected parameter name, found '{}'", token)),
+                None => return Err("Expected parameter name, found end of input".to_string()),
+            }
+            match self.next() {
+                Some(Token::Comma) => continue,
+                Some(Token::RParen) => return Ok(params),
+                Some(token) => return Err(format!("Expected ',' or ')', found '{}'", token)),
+                None => return Err("Expected ')', found end of input".to_string()),
+            }
+        }
+    }
+
+    /// Parses a comma-separated list of expressions after an opening `(` or `[`, up to and
+    /// including the matching `close` token.
+    fn parse_list(&mut self, close: Token) -> Result<Vec<Expr>, String> {
+        let mut items = Vec::new();
+        if self.peek() == Some(&close) {
+            self.next();
+            return Ok(items);
+        }
+
+        loop {
+            items.push(self.parse_expr(0)?);
+            match self.next() {
+                Some(Token::Comma) => continue,
+                Some(ref token) if *token == close => return Ok(items),
+                Some(token) => {
+                    return Err(format!("Expected ',' or '{}', found '{}'", close, token))
+                }
+                None => return Err(format!("Expected '{}', found end of input", close)),
+            }
+        }
+    }
+}
+
+/// Parses an equation between two expressions, `2x + y = 5`.
+pub fn parse_equation(input: &str) -> Result<(Expr, Expr), String> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Err("Empty equation".to_string());
+    }
+
+    let mut parser = Parser { tokens, pos: 0 };
+    let lhs = parser.parse_expr(0)?;
+    parser.expect(Token::Equals)?;
+    let rhs = parser.parse_expr(0)?;
+    match parser.peek() {
+        None => Ok((lhs, rhs)),
+        Some(token) => Err(format!("Unexpected token '{}'", token)),
+    }
+}
+
+pub fn parse(input: &str) -> Result<Statement, String> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Err("Empty expression".to_string());
+    }
+
+    let mut parser = Parser { tokens, pos: 0 };
+    let statement = match (parser.tokens.first(), parser.tokens.get(1)) {
+        (Some(Token::Ident(name)), Some(Token::Equals)) => {
+            let name = name.clone();
+            parser.pos = 2;
+            Statement::Assign(name, parser.parse_conversion()?)
+        }
+        (Some(Token::Ident(name)), Some(Token::LParen)) if parser.is_definition() => {
+            let name = name.clone();
+            parser.pos = 2;
+            let params = parser.parse_parameters()?;
+            parser.expect(Token::Equals)?;
+            Statement::Define(name, params, parser.parse_expr(0)?)
+        }
+        _ => Statement::Expr(parser.parse_conversion()?),
+    };
+
+    match parser.peek() {
+        None => Ok(statement),
+        Some(token) => Err(format!("Unexpected token '{}'", token)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn same(input: &str, parenthesized: &str) {
+        let expected = parse(parenthesized).unwrap();
+        assert_eq!(parse(input), Ok(expected), "{}", input);
+    }
+
+    #[test]
+    fn follows_operator_precedence() {
+        same("1 + 2 * 3", "1 + (2 * 3)");
+        same("8 - 4 - 2", "(8 - 4) - 2");
+        same("8 / 4 / 2", "(8 / 4) / 2");
+        same("2 ^ 3 ^ 2", "2 ^ (3 ^ 2)");
+        same("-2 ^ 2", "-(2 ^ 2)");
+        same("1 | 6 xor 3 & 5", "1 | (6 xor (3 & 5))");
+    }
+
+    #[test]
+    fn multiplies_implicitly() {
+        same("2x^2", "2 * (x ^ 2)");
+        same("3(1 + 2)", "3 * (1 + 2)");
+    }
+
+    #[test]
+    fn reads_names_after_numbers_as_units() {
+        let unit = |name: &str| Box::new(Expr::Unit(name.to_string()));
+        let number = |digits: &str| Box::new(Expr::Number(digits.to_string()));
+        assert_eq!(
+            parse("5 min"),
+            Ok(Statement::Expr(Expr::Binary(
+                BinaryOp::Multiply,
+                number("5"),
+                unit("min")
+            )))
+        );
+        let acceleration = Expr::Binary(
+            BinaryOp::Multiply,
+            number("9.81"),
+            Box::new(Expr::Binary(
+                BinaryOp::Divide,
+                unit("m"),
+                Box::new(Expr::Binary(BinaryOp::Power, unit("s"), number("2"))),
+            )),
+        );
+        assert_eq!(
+            parse("9.81 m/s^2"),
+            Ok(Statement::Expr(acceleration.clone()))
+        );
+        assert_eq!(
+            parse(&acceleration.to_string()),
+            Ok(Statement::Expr(acceleration))
+        );
+        same("6 m / 2 s", "(6 m) / (2 s)");
+        same("2 m^2 * 3", "(2 m^2) * 3");
+        same("2 km / x", "(2 km) / x");
+        assert_eq!(
+            parse("kg"),
+            Ok(Statement::Expr(Expr::Ident("kg".to_string())))
+        );
+    }
+
+    #[test]
+    fn applies_functions_without_parentheses() {
+        same("sin 45 + 1", "sin(45) + 1");
+        same("SIN(45)", "sin(45)");
+        same("7200 s to min", "(7200 s) to min");
+    }
+
+    #[test]
+    fn parses_assignments_and_definitions() {
+        assert_eq!(
+            parse("x = 2"),
+            Ok(Statement::Assign(
+                "x".to_string(),
+                Expr::Number("2".to_string())
+            ))
+        );
+        assert_eq!(
+            parse("f(a, b) = a"),
+            Ok(Statement::Define(
+                "f".to_string(),
+                vec!["a".to_string(), "b".to_string()],
+                Expr::Ident("a".to_string())
+            ))
+        );
+        assert!(parse("f(a, a) = a").is_err());
+    }
+
+    #[test]
+    fn rejects_incomplete_input() {
+        assert!(parse("").is_err());
+        assert!(parse("1 +").is_err());
+        assert!(parse("(1 + 2").is_err());
+        assert!(parse("1 2").is_err());
+        assert!(parse_equation("x + 1").is_err());
+    }
+}
@@ -0,0 +1,404 @@
+use crate::ast::{BinaryOp, Expr, UnaryOp};
+use crate::value::Value;
+use num_complex::Complex64;
+use std::f64::consts::TAU;
+
+pub const FUNCTIONS: &[&str] = &["roots"];
+
+/// Highest degree `roots` will expand or solve.
+const MAX_DEGREE: usize = 1000;
+
+/// Give up on the iterative solver after this many sweeps.
+const MAX_ITERATIONS: usize = 1000;
+
+/// Roots whose correction is below this fraction of their magnitude have converged.
+const CONVERGENCE_EPSILON: f64 = 1e-15;
+
+/// Roots closer than this (relative to their magnitude) may be one multiple root.
+const CLUSTER_EPSILON: f64 = 1e-2;
+
+/// Significant digits kept in roots found by iteration; the digits past these are noise.
+const ROOT_DIGITS: usize = 13;
+
+/// Real or imaginary parts smaller than this fraction of a root's magnitude are rounding
+/// noise.
+const REAL_EPSILON: f64 = 1e-10;
+
+/// A polynomial's coefficients, lowest power first.
+type Polynomial = Vec<Complex64>;
+
+/// A coefficient, which may be real or complex but must be a plain number.
+pub fn coefficient(value: &Value) -> Result<Complex64, String> {
+    match value {
+        Value::Quantity(_) | Value::List(_) | Value::Matrix(_) => {
+            Err("Polynomial coefficients must be numbers".to_string())
+        }
+        _ => {
+            let z = value.to_complex();
+            if z.re.is_finite() && z.im.is_finite() {
+                Ok(z)
+            } else {
+                Err("Polynomial coefficients must be finite".to_string())
+            }
+        }
+    }
+}
+
+/// Expands `expr` into a polynomial in `var`, evaluating the parts that don't mention `var`
+/// with `eval`. Only sums, products, quotients by constants and non-negative integer
+/// powers of the variable are allowed.
+pub fn expand(
+    expr: &Expr,
+    var: &str,
+    eval: &dyn Fn(&Expr) -> Result<Value, String>,
+) -> Result<Polynomial, String> {
+    if !expr.identifiers().iter().any(|name| name == var) {
+        return Ok(vec![coefficient(&eval(expr)?)?]);
+    }
+    let not_polynomial = || format!("'{}' is not a polynomial in {}", expr, var);
+    match expr {
+        Expr::Ident(_) | Expr::Unit(_) => {
+            Ok(vec![Complex64::new(0.0, 0.0), Complex64::new(1.0, 0.0)])
+        }
+        Expr::Unary(UnaryOp::Negate, operand) => Ok(expand(operand, var, eval)?
+            .into_iter()
+            .map(|c| -c)
+            .collect()),
+        Expr::Binary(op, lhs, rhs) => {
+            let a = expand(lhs, var, eval)?;
+            match op {
+                BinaryOp::Add => Ok(add(&a, &expand(rhs, var, eval)?, 1.0)),
+                BinaryOp::Subtract => Ok(add(&a, &expand(rhs, var, eval)?, -1.0)),
+                BinaryOp::Multiply => multiply(&a, &expand(rhs, var, eval)?),
+                BinaryOp::Divide => match expand(rhs, var, eval)?.as_slice() {
+                    [divisor] if *divisor != Complex64::new(0.0, 0.0) => {
+                        Ok(a.iter().map(|c| c / divisor).collect())
+                    }
+                    [_] => Err("Division by zero!".to_string()),
+                    _ => Err(not_polynomial()),
+                },
+                BinaryOp::Power => {
+                    let exponent = eval(rhs)
+                        .ok()
+                        .and_then(|value| value.to_integer())
+                        .and_then(|n| usize::try_from(n).ok())
+                        .filter(|&n| n <= MAX_DEGREE)
+                        .ok_or_else(not_polynomial)?;
+                    let mut result = vec![Complex64::new(1.0, 0.0)];
+                    for _ in 0..exponent {
+                        result = multiply(&result, &a)?;
+                    }
+                    Ok(result)
+                }
+            }
+        }
+        _ => Err(not_polynomial()),
+    }
+}
+
+fn add(a: &[Complex64], b: &[Complex64], sign: f64) -> Polynomial {
+    (0..a.len().max(b.len()))
+        .map(|i| {
+            let zero = Complex64::new(0.0, 0.0);
+            a.get(i).copied().unwrap_or(zero) + b.get(i).copied().unwrap_or(zero) * sign
+        })
+        .collect()
+}
+
+fn multiply(a: &[Complex64], b: &[Complex64]) -> Result<Polynomial, String> {
+    if a.len() + b.len() > MAX_DEGREE + 2 {
+        return Err(format!("Polynomials are limited to degree {}", MAX_DEGREE));
+    }
+    let mut product = vec![Complex64::new(0.0, 0.0); a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            product[i + j] += x * y;
+        }
+    }
+    Ok(product)
+}
+
+fn derivative(p: &[Complex64]) -> Polynomial {
+    p.iter()
+        .enumerate()
+        .skip(1)
+        .map(|(power, c)| c * power as f64)
+        .collect()
+}
+
+/// Evaluates the polynomial and its derivative at `z` by Horner's rule.
+fn evaluate(p: &[Complex64], z: Complex64) -> (Complex64, Complex64) {
+    let mut value = Complex64::new(0.0, 0.0);
+    let mut derivative = Complex64::new(0.0, 0.0);
+    for c in p.iter().rev() {
+        derivative = derivative * z + value;
+        value = value * z + c;
+    }
+    (value, derivative)
+}
+
+/// How large |p(z)| can be from rounding in Horner's rule alone; below this, `z` is as close
+/// to a root as floating point can tell.
+fn noise(p: &[Complex64], z: Complex64) -> f64 {
+    p.iter().rev().fold(0.0, |sum, c| sum * z.norm() + c.norm()) * 4.0 * f64::EPSILON
+}
+
+/// Newton's method from `z`, stopping once the steps stop shrinking.
+fn polish(p: &[Complex64], mut z: Complex64) -> Complex64 {
+    let mut last_step = f64::INFINITY;
+    for _ in 0..MAX_ITERATIONS {
+        let (value, derivative) = evaluate(p, z);
+        let step = (value / derivative).norm();
+        if step.is_nan() || step >= last_step {
+            break;
+        }
+        z -= value / derivative;
+        last_step = step;
+    }
+    z
+}
+
+fn round(x: f64) -> f64 {
+    format!("{:.*e}", ROOT_DIGITS - 1, x).parse().unwrap_or(x)
+}
+
+/// All roots of the polynomial, repeated by multiplicity: real roots in ascending order, then
+/// complex ones. Degrees one and two use closed forms, higher degrees the Aberth–Ehrlich
+/// iteration.
+pub fn roots(mut p: Polynomial) -> Result<Vec<Value>, String> {
+    while p.last() == Some(&Complex64::new(0.0, 0.0)) {
+        p.pop();
+    }
+    if p.is_empty() {
+        return Err("Every number is a root of the zero polynomial".to_string());
+    }
+    if p.len() > MAX_DEGREE + 1 {
+        return Err(format!("Polynomials are limited to degree {}", MAX_DEGREE));
+    }
+
+    // Factor out x^k exactly, so zero roots don't depend on the iteration.
+    let zeros = p.iter().take_while(|c| c.norm() == 0.0).count();
+    let p = &p[zeros..];
+    let mut found = vec![Complex64::new(0.0, 0.0); zeros];
+    found.extend(match p {
+        [_] => Vec::new(),
+        [c0, c1] => vec![-c0 / c1],
+        [c, b, a] => quadratic(*a, *b, *c),
+        _ => merge_clusters(p, aberth(p)?)
+            .into_iter()
+            .map(|z| Complex64::new(round(z.re), round(z.im)))
+            .collect(),
+    });
+
+    let mut roots: Vec<Complex64> = found
+        .into_iter()
+        .map(|z| {
+            let tolerance = REAL_EPSILON * z.norm();
+            let snap = |x: f64| if x.abs() <= tolerance { 0.0 } else { x };
+            Complex64::new(snap(z.re), snap(z.im))
+        })
+        .collect();
+    roots.sort_by(|a, b| {
+        (a.im != 0.0)
+            .cmp(&(b.im != 0.0))
+            .then(a.re.total_cmp(&b.re))
+            .then(a.im.total_cmp(&b.im))
+    });
+    Ok(roots
+        .into_iter()
+        .map(|z| {
+            if z.im == 0.0 {
+                Value::Real(z.re)
+            } else {
+                Value::Complex(z)
+            }
+        })
+        .collect())
+}
+
+/// Roots of `a x^2 + b x + c`, choosing the sign in the quadratic formula that avoids
+/// cancellation and recovering the other root from the product `c / a`.
+fn quadratic(a: Complex64, b: Complex64, c: Complex64) -> Vec<Complex64> {
+    let discriminant = (b * b - a * c * 4.0).sqrt();
+    let q = if (b.conj() * discriminant).re >= 0.0 {
+        -(b + discriminant) / 2.0
+    } else {
+        -(b - discriminant) / 2.0
+    };
+    vec![q / a, c / q]
+}
+
+/// Finds all roots at once with the Aberth–Ehrlich method, starting from points spread
+/// around a circle that encloses every root.
+fn aberth(p: &[Complex64]) -> Result<Vec<Complex64>, String> {
+    let degree = p.len() - 1;
+    let leading = p[degree];
+    let radius = 1.0
+        + p[..degree]
+            .iter()
+            .map(|c| (c / leading).norm())
+            .fold(0.0, f64::max);
+    let mut z: Vec<Complex64> = (0..degree)
+        .map(|k| Complex64::from_polar(radius, TAU * k as f64 / degree as f64 + 0.4))
+        .collect();
+    for _ in 0..MAX_ITERATIONS {
+        let mut converged = true;
+        for k in 0..degree {
+            let (value, derivative) = evaluate(p, z[k]);
+            if value.norm() <= noise(p, z[k]) {
+                continue;
+            }
+            let ratio = value / derivative;
+            let repulsion: Complex64 = (0..degree)
+                .filter(|&j| j != k)
+                .map(|j| (z[k] - z[j]).inv())
+                .sum();
+            let step = ratio / (Complex64::new(1.0, 0.0) - ratio * repulsion);
+            if !step.re.is_finite() || !step.im.is_finite() {
+                continue;
+            }
+            z[k] -= step;
+            if step.norm() > CONVERGENCE_EPSILON * z[k].norm().max(1.0) {
+                converged = false;
+            }
+        }
+        if converged {
+            return Ok(z);
+        }
+    }
+    Err(format!(
+        "Root finding did not converge after {} iterations",
+        MAX_ITERATIONS
+    ))
+}
+
+/// Iteration only resolves a root of multiplicity m to about the m-th root of machine
+/// precision, leaving m approximations scattered around it. Each such cluster is replaced by
+/// a simple root of the (m-1)-th derivative, found by Newton's method from the cluster's
+/// mean, provided that point is also a root of `p` itself.
+fn merge_clusters(p: &[Complex64], roots: Vec<Complex64>) -> Vec<Complex64> {
+    let mut merged = Vec::with_capacity(roots.len());
+    let mut remaining = roots;
+    while let Some(first) = remaining.pop() {
+        let tolerance = CLUSTER_EPSILON * first.norm().max(1.0);
+        let (mut cluster, rest): (Vec<_>, Vec<_>) = remaining
+            .into_iter()
+            .partition(|z| (z - first).norm() <= tolerance);
+        remaining = rest;
+        cluster.push(first);
+        if cluster.len() == 1 {
+            merged.push(first);
+            continue;
+        }
+
+        let mut reduced = p.to_vec();
+        for _ in 1..cluster.len() {
+            reduced = derivative(&reduced);
+        }
+        let mean = cluster.iter().sum::<Complex64>() / cluster.len() as f64;
+        let center = polish(&reduced, mean);
+        if evaluate(p, center).0.norm() <= noise(p, center) {
+            merged.extend(std::iter::repeat_n(center, cluster.len()));
+        } else {
+            merged.extend(cluster);
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Statement;
+    use crate::parser::parse;
+
+    /// The polynomial with the given real coefficients, lowest power first.
+    fn poly(coefficients: &[f64]) -> Polynomial {
+        coefficients
+            .iter()
+            .map(|&c| Complex64::new(c, 0.0))
+            .collect()
+    }
+
+    /// The monic polynomial with the given real roots.
+    fn from_roots(roots: &[f64]) -> Polynomial {
+        roots.iter().fold(poly(&[1.0]), |p, &r| {
+            multiply(&p, &poly(&[-r, 1.0])).unwrap()
+        })
+    }
+
+    fn reals(p: Polynomial) -> Vec<f64> {
+        roots(p)
+            .unwrap()
+            .into_iter()
+            .map(|root| match root {
+                Value::Real(x) => x,
+                other => panic!("expected a real root, found {}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn solves_low_degrees_in_closed_form() {
+        assert_eq!(reals(poly(&[-6.0, 2.0])), vec![3.0]);
+        assert_eq!(reals(poly(&[-4.0, 0.0, 1.0])), vec![-2.0, 2.0]);
+        assert_eq!(reals(poly(&[1.0, -1e8, 1.0])), vec![1e-8, 1e8]);
+        assert_eq!(
+            roots(poly(&[1.0, 0.0, 1.0])),
+            Ok(vec![
+                Value::Complex(Complex64::new(0.0, -1.0)),
+                Value::Complex(Complex64::new(0.0, 1.0))
+            ])
+        );
+    }
+
+    #[test]
+    fn factors_out_zero_roots_exactly() {
+        assert_eq!(reals(poly(&[0.0, 0.0, -3.0, 1.0])), vec![0.0, 0.0, 3.0]);
+        assert!(roots(poly(&[0.0, 0.0])).is_err());
+    }
+
+    #[test]
+    fn finds_complex_roots_of_unity() {
+        let found = roots(poly(&[-1.0, 0.0, 0.0, 1.0])).unwrap();
+        let half_sqrt3 = 3f64.sqrt() / 2.0;
+        assert_eq!(found[0], Value::Real(1.0));
+        for (root, im) in found[1..].iter().zip([-half_sqrt3, half_sqrt3]) {
+            let z = root.to_complex();
+            assert!((z - Complex64::new(-0.5, im)).norm() < 1e-12, "{}", z);
+        }
+    }
+
+    #[test]
+    fn merges_clusters_around_repeated_roots() {
+        assert_eq!(reals(from_roots(&[1.0, 1.0, 1.0])), vec![1.0, 1.0, 1.0]);
+        assert_eq!(
+            reals(from_roots(&[-1.0, 2.0, 2.0, 2.0, 2.0])),
+            vec![-1.0, 2.0, 2.0, 2.0, 2.0]
+        );
+    }
+
+    #[test]
+    fn keeps_close_distinct_roots_apart() {
+        assert_eq!(reals(from_roots(&[1.0, 1.001, 5.0])), vec![1.0, 1.001, 5.0]);
+    }
+
+    fn expr(text: &str) -> Expr {
+        match parse(text) {
+            Ok(Statement::Expr(expr)) => expr,
+            other => panic!("'{}' is not an expression: {:?}", text, other),
+        }
+    }
+
+    #[test]
+    fn expands_expressions_into_coefficients() {
+        let square = expr("(x - 1)^2 * 3");
+        let eval = |expr: &Expr| match expr {
+            Expr::Number(digits) => Ok(Value::Real(digits.parse().unwrap())),
+            _ => Err(format!("cannot evaluate {}", expr)),
+        };
+        assert_eq!(expand(&square, "x", &eval), Ok(poly(&[3.0, -6.0, 3.0])));
+        let not_polynomial = expr("x^x");
+        assert!(expand(&not_polynomial, "x", &eval).is_err());
+    }
+}
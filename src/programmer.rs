@@ -0,0 +1,377 @@
+use crate::ast::{BinaryOp, BitOp};
+use crate::value::Value;
+use num_bigint::{BigInt, BigUint};
+use num_integer::Integer;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+/// Largest shift accepted by `<<` and `>>`; matches the size limit on exact integer powers.
+const MAX_SHIFT: usize = 1 << 22;
+
+/// A fixed-width integer type selected with `word`, such as `i32` or `u8`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Word {
+    pub bits: u32,
+    pub signed: bool,
+}
+
+impl Word {
+    pub fn parse(name: &str) -> Option<Self> {
+        let signed = match name.chars().next()? {
+            'i' => true,
+            'u' => false,
+            _ => return None,
+        };
+        let bits = name[1..]
+            .parse()
+            .ok()
+            .filter(|bits| [8, 16, 32, 64, 128].contains(bits))?;
+        Some(Word { bits, signed })
+    }
+
+    pub fn name(self) -> String {
+        format!("{}{}", if self.signed { "i" } else { "u" }, self.bits)
+    }
+
+    fn modulus(self) -> BigInt {
+        BigInt::one() << self.bits
+    }
+
+    fn min(self) -> BigInt {
+        if self.signed {
+            -(BigInt::one() << (self.bits - 1))
+        } else {
+            BigInt::zero()
+        }
+    }
+
+    fn max(self) -> BigInt {
+        if self.signed {
+            (BigInt::one() << (self.bits - 1)) - 1
+        } else {
+            self.modulus() - 1
+        }
+    }
+
+    /// Reduces `n` modulo 2^bits into the word's range, as hardware registers do.
+    pub fn wrap(self, n: &BigInt) -> BigInt {
+        let wrapped = n.mod_floor(&self.modulus());
+        if wrapped > self.max() {
+            wrapped - self.modulus()
+        } else {
+            wrapped
+        }
+    }
+
+    /// Brings an arithmetic result into range according to the overflow mode.
+    pub fn fit(self, n: BigInt, overflow: Overflow) -> Result<BigInt, String> {
+        if n >= self.min() && n <= self.max() {
+            Ok(n)
+        } else if overflow == Overflow::Wrap {
+            Ok(self.wrap(&n))
+        } else {
+            self.overflowed(n.is_negative(), overflow)
+        }
+    }
+
+    /// Reads a hex, octal or binary literal as a bit pattern, so `0xFF` is -1 in an `i8`.
+    pub fn fit_pattern(self, n: BigInt, overflow: Overflow) -> Result<BigInt, String> {
+        if !n.is_negative() && n.bits() <= self.bits as u64 {
+            Ok(self.wrap(&n))
+        } else {
+            self.fit(n, overflow)
+        }
+    }
+
+    /// The result of a saturating or checked operation that went out of range.
+    fn overflowed(self, negative: bool, overflow: Overflow) -> Result<BigInt, String> {
+        match overflow {
+            Overflow::Saturate if negative => Ok(self.min()),
+            Overflow::Saturate => Ok(self.max()),
+            Overflow::Wrap | Overflow::Error => {
+                Err(format!("Overflow: result does not fit in {}", self.name()))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overflow {
+    Wrap,
+    Saturate,
+    Error,
+}
+
+impl Overflow {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "wrap" => Some(Overflow::Wrap),
+            "saturate" => Some(Overflow::Saturate),
+            "error" => Some(Overflow::Error),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Overflow::Wrap => "wrap",
+            Overflow::Saturate => "saturate",
+            Overflow::Error => "error",
+        }
+    }
+}
+
+/// Arithmetic on fixed-width integers: division truncates toward zero and results that leave
+/// the word's range wrap, saturate or fail depending on `overflow`.
+pub fn apply_word(
+    op: BinaryOp,
+    a: &BigInt,
+    b: &BigInt,
+    word: Word,
+    overflow: Overflow,
+) -> Result<BigInt, String> {
+    let exact = match op {
+        BinaryOp::Add => a + b,
+        BinaryOp::Subtract => a - b,
+        BinaryOp::Multiply => a * b,
+        BinaryOp::Divide => {
+            if b.is_zero() {
+                return Err("Division by zero!".to_string());
+            }
+            a / b
+        }
+        BinaryOp::Power => {
+            if b.is_negative() {
+                return Err("Negative exponents are not supported with integer words".to_string());
+            }
+            if overflow == Overflow::Wrap {
+                return Ok(word.wrap(&a.modpow(b, &word.modulus())));
+            }
+            // 0, 1 and -1 only depend on the exponent's parity; anything larger overflows
+            // once the exponent exceeds the word size.
+            let exponent = if a.magnitude() <= &BigUint::one() {
+                if b.is_zero() {
+                    0
+                } else if b.is_even() {
+                    2
+                } else {
+                    1
+                }
+            } else if let Some(exponent) = b.to_u32().filter(|&e| e <= word.bits) {
+                exponent
+            } else {
+                return word.overflowed(a.is_negative() && b.is_odd(), overflow);
+            };
+            a.pow(exponent)
+        }
+    };
+    word.fit(exact, overflow)
+}
+
+fn integer(value: &Value, symbol: &str) -> Result<BigInt, String> {
+    value
+        .to_integer()
+        .ok_or_else(|| format!("'{}' needs integer operands", symbol))
+}
+
+pub fn apply(op: BitOp, a: &Value, b: &Value) -> Result<Value, String> {
+    let x = integer(a, op.symbol())?;
+    let y = integer(b, op.symbol())?;
+    let result = match op {
+        BitOp::And => x & y,
+        BitOp::Or => x | y,
+        BitOp::Xor => x ^ y,
+        BitOp::ShiftLeft | BitOp::ShiftRight => {
+            let shift = y
+                .to_usize()
+                .filter(|&shift| shift <= MAX_SHIFT)
+                .ok_or_else(|| format!("Shift amount must be between 0 and {}", MAX_SHIFT))?;
+            if op == BitOp::ShiftLeft {
+                x << shift
+            } else {
+                x >> shift
+            }
+        }
+    };
+    Ok(Value::Integer(result))
+}
+
+/// Bitwise complement; on unbounded integers `~x` is `-x - 1`.
+pub fn not(value: &Value) -> Result<Value, String> {
+    Ok(Value::Integer(!integer(value, "~")?))
+}
+
+/// Splits a run of digits into groups of `size` from the right: `1010_0101`.
+fn group(digits: &str, size: usize) -> String {
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(size) {
+            grouped.push('_');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// `255 (0xFF, 0o377, 0b1111_1111)`, the way `mode prog` shows integer results. With a word
+/// size the other bases show the full two's-complement bit pattern: `-1 (0xFF, ...)` in `i8`.
+pub fn format_bases(n: &BigInt, word: Option<Word>) -> String {
+    if let Some(word) = word {
+        let pattern = n
+            .mod_floor(&word.modulus())
+            .to_biguint()
+            .unwrap_or_default();
+        let hex_width = word.bits.div_ceil(4) as usize;
+        return format!(
+            "{} (0x{}, 0o{:o}, 0b{})",
+            n,
+            group(&format!("{:0width$X}", pattern, width = hex_width), 4),
+            pattern,
+            group(
+                &format!("{:0width$b}", pattern, width = word.bits as usize),
+                4
+            )
+        );
+    }
+    let sign = if n.is_negative() { "-" } else { "" };
+    let magnitude = n.magnitude();
+    format!(
+        "{} ({}0x{}, {}0o{:o}, {}0b{})",
+        n,
+        sign,
+        group(&format!("{:X}", magnitude), 4),
+        sign,
+        magnitude,
+        sign,
+        group(&format!("{:b}", magnitude), 4)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Statement;
+    use crate::calculator::Calculator;
+    use crate::eval::calculate;
+    use crate::parser::parse;
+
+    const I8: Word = Word {
+        bits: 8,
+        signed: true,
+    };
+    const U8: Word = Word {
+        bits: 8,
+        signed: false,
+    };
+
+    fn int(n: i64) -> BigInt {
+        BigInt::from(n)
+    }
+
+    #[test]
+    fn parses_word_names() {
+        assert_eq!(Word::parse("i8"), Some(I8));
+        assert_eq!(
+            Word::parse("u128").map(Word::name),
+            Some("u128".to_string())
+        );
+        assert_eq!(Word::parse("i12"), None);
+        assert_eq!(Word::parse("x32"), None);
+    }
+
+    #[test]
+    fn fits_results_by_overflow_mode() {
+        assert_eq!(I8.fit(int(127), Overflow::Error), Ok(int(127)));
+        assert_eq!(I8.fit(int(128), Overflow::Wrap), Ok(int(-128)));
+        assert_eq!(I8.fit(int(-129), Overflow::Wrap), Ok(int(127)));
+        assert_eq!(I8.fit(int(300), Overflow::Saturate), Ok(int(127)));
+        assert_eq!(I8.fit(int(-300), Overflow::Saturate), Ok(int(-128)));
+        assert_eq!(U8.fit(int(-1), Overflow::Wrap), Ok(int(255)));
+        assert_eq!(U8.fit(int(-1), Overflow::Saturate), Ok(int(0)));
+        assert!(U8.fit(int(256), Overflow::Error).is_err());
+    }
+
+    #[test]
+    fn reads_literals_as_bit_patterns() {
+        assert_eq!(I8.fit_pattern(int(0xFF), Overflow::Error), Ok(int(-1)));
+        assert_eq!(I8.fit_pattern(int(0x80), Overflow::Error), Ok(int(-128)));
+        assert!(I8.fit_pattern(int(0x100), Overflow::Error).is_err());
+    }
+
+    #[test]
+    fn applies_arithmetic_within_the_word() {
+        let apply = |op, a, b, overflow| apply_word(op, &int(a), &int(b), I8, overflow);
+        assert_eq!(apply(BinaryOp::Add, 100, 100, Overflow::Wrap), Ok(int(-56)));
+        assert_eq!(
+            apply(BinaryOp::Add, 100, 100, Overflow::Saturate),
+            Ok(int(127))
+        );
+        assert!(apply(BinaryOp::Add, 100, 100, Overflow::Error).is_err());
+        assert_eq!(
+            apply(BinaryOp::Subtract, -100, 100, Overflow::Saturate),
+            Ok(int(-128))
+        );
+        assert_eq!(apply(BinaryOp::Divide, -7, 2, Overflow::Error), Ok(int(-3)));
+        assert_eq!(
+            apply(BinaryOp::Divide, -128, -1, Overflow::Wrap),
+            Ok(int(-128))
+        );
+        assert!(apply(BinaryOp::Divide, 1, 0, Overflow::Wrap).is_err());
+    }
+
+    #[test]
+    fn raises_powers_within_the_word() {
+        let power =
+            |a, b: i64, overflow| apply_word(BinaryOp::Power, &int(a), &int(b), I8, overflow);
+        assert_eq!(power(2, 7, Overflow::Wrap), Ok(int(-128)));
+        assert_eq!(
+            power(3, 1000, Overflow::Wrap),
+            Ok(I8.wrap(&int(3).pow(1000u32)))
+        );
+        assert_eq!(power(2, 1000, Overflow::Saturate), Ok(int(127)));
+        assert_eq!(power(-2, 1001, Overflow::Saturate), Ok(int(-128)));
+        assert_eq!(power(-1, 1_000_001, Overflow::Error), Ok(int(-1)));
+        assert!(power(2, 7, Overflow::Error).is_err());
+        assert!(power(2, -1, Overflow::Wrap).is_err());
+    }
+
+    fn evaluate(input: &str, word: &str, overflow: &str) -> Result<Value, String> {
+        let mut calc = Calculator::new();
+        calc.set_mode("prog").unwrap();
+        calc.set_word(word).unwrap();
+        calc.set_overflow(overflow).unwrap();
+        match parse(input)? {
+            Statement::Expr(expr) => calculate(&expr, &calc),
+            _ => panic!("'{}' is not an expression", input),
+        }
+    }
+
+    #[test]
+    fn reads_the_most_negative_value_as_a_literal() {
+        let min = |n: i64| Ok(Value::Integer(int(n)));
+        assert_eq!(evaluate("-128", "i8", "error"), min(-128));
+        assert_eq!(evaluate("-128", "i8", "saturate"), min(-128));
+        assert_eq!(evaluate("-0x80", "i8", "error"), min(-128));
+        assert_eq!(evaluate("-2147483648", "i32", "saturate"), min(-2147483648));
+        assert!(evaluate("-129", "i8", "error").is_err());
+        assert_eq!(evaluate("-129", "i8", "saturate"), min(-128));
+        assert_eq!(evaluate("-129", "i8", "wrap"), min(127));
+        assert_eq!(evaluate("-(1 + 127)", "i8", "saturate"), min(-127));
+    }
+
+    #[test]
+    fn reads_large_literals_exactly() {
+        assert_eq!(
+            evaluate("18446744073709551615", "u64", "error"),
+            Ok(Value::Integer(BigInt::from(u64::MAX)))
+        );
+    }
+
+    #[test]
+    fn shows_twos_complement_patterns() {
+        assert_eq!(
+            format_bases(&int(-1), Some(I8)),
+            "-1 (0xFF, 0o377, 0b1111_1111)"
+        );
+        assert_eq!(format_bases(&int(-10), None), "-10 (-0xA, -0o12, -0b1010)");
+    }
+}
@@ -0,0 +1,352 @@
+use crate::ast::{BinaryOp, Expr};
+use crate::calculator::{Calculator, HistoryRef};
+use crate::constants;
+use crate::eval::calculate_with;
+use crate::matrix::{format_entry, Matrix};
+use crate::parser::parse_equation;
+use crate::units::DIMENSIONLESS;
+use crate::value::Value;
+use colored::*;
+use std::collections::HashMap;
+
+/// Relative tolerance for the check that an equation really is linear in its unknowns.
+const LINEARITY_EPSILON: f64 = 1e-9;
+
+/// The solution of a linear system: each determined unknown with its value, or, when the
+/// system is underdetermined, its expression in terms of the free parameters. `substituted`
+/// lists the names that were not unknowns because they already had a value, and `shadowed`
+/// the unknowns that are also the names of constants.
+pub struct Solution {
+    pub values: Vec<(String, String)>,
+    pub free: Vec<String>,
+    pub substituted: Vec<String>,
+    pub shadowed: Vec<String>,
+}
+
+impl Solution {
+    pub fn print(&self) {
+        for note in notes(&self.substituted, &self.shadowed) {
+            let mut note = note;
+            note[..1].make_ascii_uppercase();
+            println!("{}", note.bright_green());
+        }
+        if !self.free.is_empty() {
+            println!(
+                "{}",
+                format!(
+                    "Infinitely many solutions; free parameters: {}",
+                    self.free.join(", ")
+                )
+                .bright_green()
+            );
+        }
+        for (name, value) in &self.values {
+            println!("{} {} {}", name, "=".bright_green(), value);
+        }
+    }
+}
+
+/// Solves linear equations separated by `;`, such as `2x + y = 5; x - y = 1`. Names that are
+/// not variables, previous results or dimensionless constants are the unknowns.
+pub fn solve(input: &str, calc: &Calculator) -> Result<Solution, String> {
+    let equations = input
+        .split(';')
+        .map(str::trim)
+        .filter(|equation| !equation.is_empty())
+        .map(|equation| {
+            let (lhs, rhs) = parse_equation(equation)?;
+            Ok(Expr::Binary(
+                BinaryOp::Subtract,
+                Box::new(lhs),
+                Box::new(rhs),
+            ))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if equations.is_empty() {
+        return Err("Usage: solve <equation>; <equation>; ...".to_string());
+    }
+
+    let mut unknowns: Vec<String> = Vec::new();
+    let mut substituted: Vec<String> = Vec::new();
+    for name in equations.iter().flat_map(Expr::identifiers) {
+        let names = if !is_known(&name, calc) {
+            &mut unknowns
+        } else if HistoryRef::parse(&name).is_none() {
+            &mut substituted
+        } else {
+            continue;
+        };
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    let shadowed: Vec<String> = unknowns
+        .iter()
+        .filter(|name| constants::lookup(name).is_some())
+        .cloned()
+        .collect();
+    // A variable left over from earlier is easy to forget, and so is a constant of the same
+    // name, so errors say which names were taken which way.
+    let notes = notes(&substituted, &shadowed);
+    let explain = |error: String| {
+        if notes.is_empty() {
+            error
+        } else {
+            format!("{} ({})", error, notes.join("; "))
+        }
+    };
+    if unknowns.is_empty() {
+        return Err(explain("There are no unknowns to solve for".to_string()));
+    }
+
+    let n = unknowns.len();
+    let mut system = Matrix::new(equations.len(), n + 1);
+    for (row, equation) in equations.iter().enumerate() {
+        let (coefficients, constant) =
+            linearize(equation, row + 1, &unknowns, calc).map_err(explain)?;
+        for (col, coefficient) in coefficients.into_iter().enumerate() {
+            system.set(row, col, coefficient);
+        }
+        system.set(row, n, -constant);
+    }
+
+    let (pivots, _) = system.row_reduce();
+    if pivots.contains(&n) {
+        return Err(explain(
+            "The system is inconsistent and has no solution".to_string(),
+        ));
+    }
+    let free: Vec<usize> = (0..n).filter(|col| !pivots.contains(col)).collect();
+    let values = pivots
+        .iter()
+        .enumerate()
+        .map(|(row, &col)| {
+            let terms: Vec<(f64, &str)> = free
+                .iter()
+                .map(|&f| (-system.get(row, f), unknowns[f].as_str()))
+                .collect();
+            (
+                unknowns[col].clone(),
+                format_linear(system.get(row, n), &terms),
+            )
+        })
+        .collect();
+    Ok(Solution {
+        values,
+        free: free.iter().map(|&f| unknowns[f].clone()).collect(),
+        substituted,
+        shadowed,
+    })
+}
+
+/// Says which names were replaced by their current values and which unknowns are also
+/// the names of constants: `using the current values of x`, `solving for c, not the constant`.
+fn notes(substituted: &[String], shadowed: &[String]) -> Vec<String> {
+    let mut notes = Vec::new();
+    if !substituted.is_empty() {
+        notes.push(format!(
+            "using the current values of {}",
+            substituted.join(", ")
+        ));
+    }
+    if !shadowed.is_empty() {
+        let constants = if shadowed.len() == 1 {
+            "the constant"
+        } else {
+            "the constants"
+        };
+        notes.push(format!(
+            "solving for {}, not {}",
+            shadowed.join(", "),
+            constants
+        ));
+    }
+    notes
+}
+
+/// Whether `name` already has a value; anything else in an equation is an unknown, even
+/// if it also names a unit or a physical constant.
+fn is_known(name: &str, calc: &Calculator) -> bool {
+    calc.variable(name).is_some()
+        || HistoryRef::parse(name).is_some()
+        || constants::lookup(name).is_some_and(|constant| constant.dimension == DIMENSIONLESS)
+}
+
+/// Evaluates `expr` with the unknowns bound to `point`.
+fn evaluate(
+    expr: &Expr,
+    unknowns: &[String],
+    point: &[f64],
+    calc: &Calculator,
+) -> Result<f64, String> {
+    let locals: HashMap<String, Value> = unknowns
+        .iter()
+        .cloned()
+        .zip(point.iter().map(|&x| Value::Real(x)))
+        .collect();
+    match calculate_with(expr, calc, locals)? {
+        Value::Quantity(_) => Err("solve needs equations between plain numbers".to_string()),
+        value => Ok(value.to_f64()),
+    }
+}
+
+/// Reads off the coefficients and constant term of `expr` by evaluating it at a few points,
+/// then checks the result at points that weren't used to build it.
+fn linearize(
+    expr: &Expr,
+    number: usize,
+    unknowns: &[String],
+    calc: &Calculator,
+) -> Result<(Vec<f64>, f64), String> {
+    let not_linear = || {
+        format!(
+            "Equation {} is not linear in {}",
+            number,
+            unknowns.join(", ")
+        )
+    };
+    let n = unknowns.len();
+    let base = vec![1.0; n];
+    let at_base = evaluate(expr, unknowns, &base, calc)?;
+    if !at_base.is_finite() {
+        return Err(format!(
+            "Equation {} does not evaluate to a real number",
+            number
+        ));
+    }
+
+    let mut coefficients = Vec::with_capacity(n);
+    for j in 0..n {
+        let mut point = base.clone();
+        point[j] += 1.0;
+        coefficients.push(evaluate(expr, unknowns, &point, calc)? - at_base);
+    }
+    let constant = at_base - coefficients.iter().sum::<f64>();
+
+    for check in [
+        |j: usize| 0.5 + 0.75 * j as f64,
+        |j: usize| -1.25 - 0.5 * j as f64,
+    ] {
+        let point: Vec<f64> = (0..n).map(check).collect();
+        let actual = evaluate(expr, unknowns, &point, calc).map_err(|_| not_linear())?;
+        let terms: Vec<f64> = coefficients
+            .iter()
+            .zip(&point)
+            .map(|(a, x)| a * x)
+            .collect();
+        let predicted = constant + terms.iter().sum::<f64>();
+        let scale =
+            1.0 + actual.abs() + constant.abs() + terms.iter().map(|t| t.abs()).sum::<f64>();
+        let error = (actual - predicted).abs();
+        if error.is_nan() || error > LINEARITY_EPSILON * scale {
+            return Err(not_linear());
+        }
+    }
+    Ok((coefficients, constant))
+}
+
+/// Writes `constant + Σ coefficient * name` readably: `3 - y`, `0.5 - 2*z`, `y`.
+fn format_linear(constant: f64, terms: &[(f64, &str)]) -> String {
+    let mut text = String::new();
+    if constant != 0.0 {
+        text = format_entry(constant, 0.0);
+    }
+    for &(coefficient, name) in terms {
+        if coefficient == 0.0 {
+            continue;
+        }
+        let sign = if coefficient < 0.0 { "-" } else { "+" };
+        text = match (text.is_empty(), sign) {
+            (true, "-") => "-".to_string(),
+            (true, _) => String::new(),
+            (false, _) => format!("{} {} ", text, sign),
+        };
+        if coefficient.abs() != 1.0 {
+            text.push_str(&format!("{}*", format_entry(coefficient.abs(), 0.0)));
+        }
+        text.push_str(name);
+    }
+    if text.is_empty() {
+        "0".to_string()
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(solution: &Solution) -> Vec<(&str, &str)> {
+        solution
+            .values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn solves_determined_systems() {
+        let calc = Calculator::new();
+        let solution = solve("2x + y = 5; x - y = 1", &calc).unwrap();
+        assert_eq!(values(&solution), vec![("x", "2"), ("y", "1")]);
+        assert!(solution.free.is_empty());
+    }
+
+    #[test]
+    fn expresses_underdetermined_systems_in_free_parameters() {
+        let calc = Calculator::new();
+        let solution = solve("x + y + 2z = 3", &calc).unwrap();
+        assert_eq!(values(&solution), vec![("x", "3 - y - 2*z")]);
+        assert_eq!(solution.free, vec!["y", "z"]);
+    }
+
+    #[test]
+    fn rejects_inconsistent_and_nonlinear_systems() {
+        let calc = Calculator::new();
+        assert!(solve("x + y = 1; x + y = 2", &calc).is_err());
+        assert_eq!(
+            solve("x * y = 2", &calc).err(),
+            Some("Equation 1 is not linear in x, y".to_string())
+        );
+    }
+
+    #[test]
+    fn solves_systems_with_small_coefficients() {
+        let calc = Calculator::new();
+        let solution = solve("1e-12 x = 1e-12", &calc).unwrap();
+        assert_eq!(values(&solution), vec![("x", "1")]);
+        let solution = solve(
+            "1e-11 x + 1e-11 y = 3e-11; 1e-11 x - 1e-11 y = 1e-11",
+            &calc,
+        )
+        .unwrap();
+        assert_eq!(values(&solution), vec![("x", "2"), ("y", "1")]);
+        assert!(solution.free.is_empty());
+    }
+
+    #[test]
+    fn names_the_variables_it_substitutes() {
+        let mut calc = Calculator::new();
+        calc.set_variable("x", Value::Real(5.0)).unwrap();
+        let solution = solve("x * y = 2", &calc).unwrap();
+        assert_eq!(values(&solution), vec![("y", "0.4")]);
+        assert_eq!(solution.substituted, vec!["x"]);
+        assert_eq!(
+            solve("x = 1", &calc).err(),
+            Some("There are no unknowns to solve for (using the current values of x)".to_string())
+        );
+    }
+
+    #[test]
+    fn names_the_unknowns_that_shadow_constants() {
+        let calc = Calculator::new();
+        let solution = solve("c + G = 1; c - G = 0", &calc).unwrap();
+        assert_eq!(values(&solution), vec![("c", "0.5"), ("G", "0.5")]);
+        assert_eq!(solution.shadowed, vec!["c", "G"]);
+        assert_eq!(
+            solve("c * x = 4", &calc).err(),
+            Some("Equation 1 is not linear in c, x (solving for c, not the constant)".to_string())
+        );
+    }
+}
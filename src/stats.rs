@@ -0,0 +1,194 @@
+use crate::value::Value;
+
+pub const FUNCTIONS: &[&str] = &[
+    "mean",
+    "median",
+    "mode",
+    "var",
+    "varp",
+    "stdev",
+    "stdevp",
+    "min",
+    "max",
+    "sum",
+    "percentile",
+    "quartiles",
+];
+
+/// Evaluates a statistics function. The data is either a single list, `mean([1, 2, 3])`, or
+/// the arguments themselves, `mean(1, 2, 3)`; `percentile` takes a list and a percentage.
+pub fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+    if name == "percentile" {
+        return match args {
+            [Value::List(items), p] => {
+                let p = p.to_f64();
+                if !(0.0..=100.0).contains(&p) {
+                    return Err("Percentile must be between 0 and 100".to_string());
+                }
+                Ok(Value::Real(percentile(&sorted(&numbers(name, items)?), p)))
+            }
+            _ => Err("Usage: percentile(list, p)".to_string()),
+        };
+    }
+
+    let items = match args {
+        [Value::List(items)] => items.as_slice(),
+        _ => args,
+    };
+    let xs = numbers(name, items)?;
+    let n = xs.len() as f64;
+    let result = match name {
+        // `min` and `max` return the element itself, so exact values stay exact.
+        "min" | "max" => {
+            let pick = if name == "min" { f64::lt } else { f64::gt };
+            let mut best = 0;
+            for (i, x) in xs.iter().enumerate() {
+                if pick(x, &xs[best]) {
+                    best = i;
+                }
+            }
+            return Ok(items[best].clone());
+        }
+        "sum" => xs.iter().sum(),
+        "mean" => mean(&xs),
+        "median" => percentile(&sorted(&xs), 50.0),
+        "mode" => return Ok(mode(&xs)),
+        "var" | "stdev" | "varp" | "stdevp" => {
+            let sample = name == "var" || name == "stdev";
+            if sample && xs.len() < 2 {
+                return Err(format!("'{}' needs at least two values", name));
+            }
+            let mean = mean(&xs);
+            let squares: f64 = xs.iter().map(|x| (x - mean).powi(2)).sum();
+            let variance = squares / if sample { n - 1.0 } else { n };
+            if name.starts_with("stdev") {
+                variance.sqrt()
+            } else {
+                variance
+            }
+        }
+        "quartiles" => {
+            let xs = sorted(&xs);
+            return Ok(Value::List(
+                [25.0, 50.0, 75.0]
+                    .iter()
+                    .map(|&p| Value::Real(percentile(&xs, p)))
+                    .collect(),
+            ));
+        }
+        _ => return Err(format!("Unknown function '{}'", name)),
+    };
+    Ok(Value::Real(result))
+}
+
+/// The data as floats; statistics are only defined on non-empty lists of real numbers.
+fn numbers(name: &str, items: &[Value]) -> Result<Vec<f64>, String> {
+    if items.is_empty() {
+        return Err(format!("'{}' needs at least one value", name));
+    }
+    items
+        .iter()
+        .map(|item| match item {
+            Value::List(_) | Value::Matrix(_) => {
+                Err(format!("'{}' expects a flat list of numbers", name))
+            }
+            Value::Quantity(_) => Err(format!("'{}' requires dimensionless values", name)),
+            _ if item.to_f64().is_nan() => {
+                Err(format!("'{}' is only defined for real numbers", name))
+            }
+            _ => Ok(item.to_f64()),
+        })
+        .collect()
+}
+
+fn sorted(xs: &[f64]) -> Vec<f64> {
+    let mut xs = xs.to_vec();
+    xs.sort_by(f64::total_cmp);
+    xs
+}
+
+fn mean(xs: &[f64]) -> f64 {
+    xs.iter().sum::<f64>() / xs.len() as f64
+}
+
+/// Linear interpolation between closest ranks, as in spreadsheets' PERCENTILE.INC.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = p / 100.0 * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+/// The most frequent value, or a list of them when several tie.
+fn mode(xs: &[f64]) -> Value {
+    let xs = sorted(xs);
+    let mut runs: Vec<(f64, usize)> = Vec::new();
+    for x in xs {
+        match runs.last_mut() {
+            Some((value, count)) if *value == x => *count += 1,
+            _ => runs.push((x, 1)),
+        }
+    }
+    let most = runs.iter().map(|&(_, count)| count).max().unwrap_or(0);
+    let mut modes: Vec<Value> = runs
+        .into_iter()
+        .filter(|&(_, count)| count == most)
+        .map(|(value, _)| Value::Real(value))
+        .collect();
+    if modes.len() == 1 {
+        modes.remove(0)
+    } else {
+        Value::List(modes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(xs: &[f64]) -> Value {
+        Value::List(xs.iter().map(|&x| Value::Real(x)).collect())
+    }
+
+    fn stat(name: &str, xs: &[f64]) -> Result<Value, String> {
+        call(name, &[list(xs)])
+    }
+
+    #[test]
+    fn finds_the_most_frequent_values() {
+        assert_eq!(stat("mode", &[1.0, 2.0, 2.0]), Ok(Value::Real(2.0)));
+        assert_eq!(
+            stat("mode", &[3.0, 1.0, 3.0, 1.0, 2.0]),
+            Ok(list(&[1.0, 3.0]))
+        );
+    }
+
+    #[test]
+    fn computes_averages_and_spread() {
+        let data = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        assert_eq!(stat("mean", &data), Ok(Value::Real(5.0)));
+        assert_eq!(stat("median", &data), Ok(Value::Real(4.5)));
+        assert_eq!(stat("stdevp", &data), Ok(Value::Real(2.0)));
+        assert_eq!(stat("varp", &data), Ok(Value::Real(4.0)));
+        assert_eq!(stat("var", &[1.0, 3.0]), Ok(Value::Real(2.0)));
+        assert!(stat("var", &[1.0]).is_err());
+    }
+
+    #[test]
+    fn interpolates_percentiles() {
+        let data = list(&[4.0, 1.0, 3.0, 2.0]);
+        assert_eq!(
+            call("percentile", &[data.clone(), Value::Real(25.0)]),
+            Ok(Value::Real(1.75))
+        );
+        assert_eq!(call("quartiles", &[data]), Ok(list(&[1.75, 2.5, 3.25])));
+    }
+
+    #[test]
+    fn accepts_values_as_arguments() {
+        let args = [Value::Real(3.0), Value::Real(1.0), Value::Real(2.0)];
+        assert_eq!(call("min", &args), Ok(Value::Real(1.0)));
+        assert_eq!(call("sum", &args), Ok(Value::Real(6.0)));
+        assert!(call("mean", &[]).is_err());
+    }
+}
@@ -0,0 +1,548 @@
+use crate::ast::{BinaryOp, Expr, UnaryOp};
+use crate::calculator::{AngleMode, Calculator};
+use crate::eval::is_builtin;
+use std::collections::HashMap;
+
+pub const FUNCTIONS: &[&str] = &["diff"];
+
+/// Deepest nesting of user functions inlined while differentiating; recursive functions
+/// have no closed-form derivative.
+const MAX_INLINE_DEPTH: usize = 64;
+
+/// The simplified derivative of `diff(expr, x)`'s first argument with respect to `x`.
+pub fn differentiate(args: &[Expr], calc: &Calculator) -> Result<Expr, String> {
+    match args {
+        [expr, Expr::Ident(var)] => {
+            let differentiator = Differentiator { var, calc };
+            Ok(simplify(&differentiator.derive(expr, 0)?))
+        }
+        _ => Err("Usage: diff(expr, x) or diff(expr, x, at)".to_string()),
+    }
+}
+
+struct Differentiator<'a> {
+    var: &'a str,
+    calc: &'a Calculator,
+}
+
+impl Differentiator<'_> {
+    fn derive(&self, expr: &Expr, depth: usize) -> Result<Expr, String> {
+        if !expr.identifiers().iter().any(|name| name == self.var) {
+            return Ok(number(0.0));
+        }
+        match expr {
+            Expr::Ident(_) | Expr::Unit(_) => Ok(number(1.0)),
+            Expr::Unary(UnaryOp::Negate, operand) => Ok(neg(self.derive(operand, depth)?)),
+            Expr::Binary(op, u, v) => {
+                let du = self.derive(u, depth)?;
+                let dv = self.derive(v, depth)?;
+                let (u, v) = (u.as_ref().clone(), v.as_ref().clone());
+                Ok(match op {
+                    BinaryOp::Add => binary(BinaryOp::Add, du, dv),
+                    BinaryOp::Subtract => binary(BinaryOp::Subtract, du, dv),
+                    BinaryOp::Multiply => binary(
+                        BinaryOp::Add,
+                        binary(BinaryOp::Multiply, du, v),
+                        binary(BinaryOp::Multiply, u, dv),
+                    ),
+                    BinaryOp::Divide => binary(
+                        BinaryOp::Divide,
+                        binary(
+                            BinaryOp::Subtract,
+                            binary(BinaryOp::Multiply, du, v.clone()),
+                            binary(BinaryOp::Multiply, u, dv),
+                        ),
+                        binary(BinaryOp::Power, v, number(2.0)),
+                    ),
+                    BinaryOp::Power => self.derive_power(u, v, du, dv),
+                })
+            }
+            Expr::Call(name, args) => self.derive_call(name, args, depth),
+            _ => Err(format!("Cannot differentiate '{}'", expr)),
+        }
+    }
+
+    /// d(u^v): the power rule when only the base varies, the exponential rule when only the
+    /// exponent does, and `u^v * (v' ln u + v u' / u)` in general.
+    fn derive_power(&self, u: Expr, v: Expr, du: Expr, dv: Expr) -> Expr {
+        let power = binary(BinaryOp::Power, u.clone(), v.clone());
+        if dv == number(0.0) {
+            let lowered = binary(
+                BinaryOp::Power,
+                u,
+                binary(BinaryOp::Subtract, v.clone(), number(1.0)),
+            );
+            return binary(
+                BinaryOp::Multiply,
+                binary(BinaryOp::Multiply, v, lowered),
+                du,
+            );
+        }
+        let ln = call("ln", u.clone());
+        if du == number(0.0) {
+            return binary(
+                BinaryOp::Multiply,
+                binary(BinaryOp::Multiply, power, ln),
+                dv,
+            );
+        }
+        binary(
+            BinaryOp::Multiply,
+            power,
+            binary(
+                BinaryOp::Add,
+                binary(BinaryOp::Multiply, dv, ln),
+                binary(BinaryOp::Divide, binary(BinaryOp::Multiply, v, du), u),
+            ),
+        )
+    }
+
+    fn derive_call(&self, name: &str, args: &[Expr], depth: usize) -> Result<Expr, String> {
+        if let Some(function) = self.calc.function(name) {
+            if depth >= MAX_INLINE_DEPTH {
+                return Err(format!(
+                    "Cannot differentiate '{}': it is recursive or nested too deeply",
+                    name
+                ));
+            }
+            if args.len() != function.params.len() {
+                return Err(format!(
+                    "Function '{}' expects {} argument(s), got {}",
+                    name,
+                    function.params.len(),
+                    args.len()
+                ));
+            }
+            let bindings: HashMap<String, Expr> =
+                function.params.iter().cloned().zip(args.to_vec()).collect();
+            return self.derive(&function.body.substitute(&bindings), depth + 1);
+        }
+
+        match (name, args) {
+            ("diff", [_, _]) => {
+                let inner = differentiate(args, self.calc)?;
+                self.derive(&inner, depth)
+            }
+            ("atan2", [y, x]) => {
+                // d atan2(y, x) = (x y' - y x') / (x^2 + y^2)
+                let numerator = binary(
+                    BinaryOp::Subtract,
+                    binary(BinaryOp::Multiply, x.clone(), self.derive(y, depth)?),
+                    binary(BinaryOp::Multiply, y.clone(), self.derive(x, depth)?),
+                );
+                let denominator = binary(
+                    BinaryOp::Add,
+                    binary(BinaryOp::Power, x.clone(), number(2.0)),
+                    binary(BinaryOp::Power, y.clone(), number(2.0)),
+                );
+                Ok(self.inverse_trig_derivative(binary(BinaryOp::Divide, numerator, denominator)))
+            }
+            (_, [u]) => {
+                let outer = self.outer_derivative(name, u)?;
+                Ok(binary(BinaryOp::Multiply, outer, self.derive(u, depth)?))
+            }
+            _ => Err(unsupported(name)),
+        }
+    }
+
+    /// The derivative of a one-argument built-in `name` at `u`, before the chain rule.
+    fn outer_derivative(&self, name: &str, u: &Expr) -> Result<Expr, String> {
+        let f = |function: &str| call(function, u.clone());
+        let square = |e: Expr| binary(BinaryOp::Power, e, number(2.0));
+        let u_squared = square(u.clone());
+        let reciprocal = |e: Expr| binary(BinaryOp::Divide, number(1.0), e);
+        let root = |e: Expr| call("sqrt", e);
+        Ok(match name {
+            "sqrt" => reciprocal(binary(BinaryOp::Multiply, number(2.0), f("sqrt"))),
+            "sin" => self.trig_derivative(f("cos")),
+            "cos" => self.trig_derivative(neg(f("sin"))),
+            "tan" => self.trig_derivative(square(f("sec"))),
+            "sec" => self.trig_derivative(binary(BinaryOp::Multiply, f("sec"), f("tan"))),
+            "csc" => self.trig_derivative(neg(binary(BinaryOp::Multiply, f("csc"), f("cot")))),
+            "cot" => self.trig_derivative(neg(square(f("csc")))),
+            "asin" => self.inverse_trig_derivative(reciprocal(root(binary(
+                BinaryOp::Subtract,
+                number(1.0),
+                u_squared,
+            )))),
+            "acos" => self.inverse_trig_derivative(neg(reciprocal(root(binary(
+                BinaryOp::Subtract,
+                number(1.0),
+                u_squared,
+            ))))),
+            "atan" => self.inverse_trig_derivative(reciprocal(binary(
+                BinaryOp::Add,
+                number(1.0),
+                u_squared,
+            ))),
+            "sinh" => f("cosh"),
+            "cosh" => f("sinh"),
+            "tanh" => reciprocal(square(f("cosh"))),
+            "asinh" => reciprocal(root(binary(BinaryOp::Add, u_squared, number(1.0)))),
+            "acosh" => reciprocal(root(binary(BinaryOp::Subtract, u_squared, number(1.0)))),
+            "atanh" => reciprocal(binary(BinaryOp::Subtract, number(1.0), u_squared)),
+            "ln" => reciprocal(u.clone()),
+            "log" => reciprocal(binary(
+                BinaryOp::Multiply,
+                u.clone(),
+                call("ln", number(10.0)),
+            )),
+            "abs" => f("sign"),
+            "sign" => number(0.0),
+            "gamma" => binary(BinaryOp::Multiply, f("gamma"), f("digamma")),
+            "lgamma" => f("digamma"),
+            "fact" => {
+                let shifted = binary(BinaryOp::Add, u.clone(), number(1.0));
+                binary(
+                    BinaryOp::Multiply,
+                    call("gamma", shifted.clone()),
+                    call("digamma", shifted),
+                )
+            }
+            _ => return Err(unsupported(name)),
+        })
+    }
+
+    /// Trig functions of an angle in degrees or gradians pick up a factor of π/180 or π/200.
+    fn trig_derivative(&self, derivative: Expr) -> Expr {
+        match self.half_turn() {
+            Some(units) => binary(
+                BinaryOp::Multiply,
+                binary(BinaryOp::Divide, pi(), number(units)),
+                derivative,
+            ),
+            None => derivative,
+        }
+    }
+
+    /// Inverse trig functions returning degrees or gradians pick up 180/π or 200/π.
+    fn inverse_trig_derivative(&self, derivative: Expr) -> Expr {
+        match self.half_turn() {
+            Some(units) => binary(
+                BinaryOp::Multiply,
+                binary(BinaryOp::Divide, number(units), pi()),
+                derivative,
+            ),
+            None => derivative,
+        }
+    }
+
+    /// The number of angle units in a half turn, or `None` in radians.
+    fn half_turn(&self) -> Option<f64> {
+        match self.calc.angle_mode {
+            AngleMode::Radians => None,
+            AngleMode::Degrees => Some(180.0),
+            AngleMode::Gradians => Some(200.0),
+        }
+    }
+}
+
+fn unsupported(name: &str) -> String {
+    if is_builtin(name) {
+        format!("Cannot differentiate '{}'", name)
+    } else {
+        format!("Unknown function '{}'", name)
+    }
+}
+
+fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+    Expr::Binary(op, Box::new(lhs), Box::new(rhs))
+}
+
+fn pi() -> Expr {
+    Expr::Ident("pi".to_string())
+}
+
+fn call(name: &str, arg: Expr) -> Expr {
+    Expr::Call(name.to_string(), vec![arg])
+}
+
+/// A numeric literal; negative values become a negation so they print as `-2`.
+fn number(value: f64) -> Expr {
+    if value < 0.0 {
+        Expr::Unary(
+            UnaryOp::Negate,
+            Box::new(Expr::Number((-value).to_string())),
+        )
+    } else {
+        Expr::Number(value.abs().to_string())
+    }
+}
+
+/// The value of a numeric literal, possibly negated.
+fn literal(expr: &Expr) -> Option<f64> {
+    match expr {
+        Expr::Number(digits) => digits.parse().ok(),
+        Expr::Unary(UnaryOp::Negate, operand) => literal(operand).map(|value| -value),
+        _ => None,
+    }
+}
+
+/// Whether `expr` is a number, possibly written with `pi` and `e`, such as `pi / 180`.
+fn is_constant(expr: &Expr) -> bool {
+    expr.identifiers()
+        .iter()
+        .all(|name| name == "pi" || name == "e")
+}
+
+fn has_constant_factor(expr: &Expr) -> bool {
+    match expr {
+        Expr::Binary(BinaryOp::Multiply, a, _) => has_constant_factor(a),
+        _ => is_constant(expr),
+    }
+}
+
+fn neg(expr: Expr) -> Expr {
+    match expr {
+        Expr::Unary(UnaryOp::Negate, operand) => *operand,
+        Expr::Binary(BinaryOp::Subtract, a, b) => Expr::Binary(BinaryOp::Subtract, b, a),
+        // Negate the leading factor of a product when it is a constant: -2 * x, -pi / 180 * y.
+        Expr::Binary(BinaryOp::Multiply, a, b) if has_constant_factor(&a) => multiply(neg(*a), *b),
+        // -(a / b) = -a / b, which reads without parentheses.
+        Expr::Binary(BinaryOp::Divide, a, b) => divide(neg(*a), *b),
+        _ => match literal(&expr) {
+            Some(value) => number(-value),
+            None => Expr::Unary(UnaryOp::Negate, Box::new(expr)),
+        },
+    }
+}
+
+/// Folds constants and removes the zeros, ones and double negations that differentiation
+/// rules leave behind.
+pub fn simplify(expr: &Expr) -> Expr {
+    match expr {
+        Expr::Unary(UnaryOp::Negate, operand) => neg(simplify(operand)),
+        Expr::Binary(BinaryOp::Add, a, b) => add(simplify(a), simplify(b)),
+        Expr::Binary(BinaryOp::Subtract, a, b) => subtract(simplify(a), simplify(b)),
+        Expr::Binary(BinaryOp::Multiply, a, b) => multiply(simplify(a), simplify(b)),
+        Expr::Binary(BinaryOp::Divide, a, b) => divide(simplify(a), simplify(b)),
+        Expr::Binary(BinaryOp::Power, a, b) => power(simplify(a), simplify(b)),
+        Expr::Call(name, args) => {
+            let args: Vec<Expr> = args.iter().map(simplify).collect();
+            match (name.as_str(), args.as_slice()) {
+                ("ln", [Expr::Ident(e)]) if e == "e" => number(1.0),
+                _ => Expr::Call(name.clone(), args),
+            }
+        }
+        _ => expr.clone(),
+    }
+}
+
+/// A numeric literal or a quotient of two, as a numerator and denominator.
+fn fraction(expr: &Expr) -> Option<(f64, f64)> {
+    match expr {
+        Expr::Binary(BinaryOp::Divide, a, b) => Some((literal(a)?, literal(b)?)),
+        _ => literal(expr).map(|value| (value, 1.0)),
+    }
+}
+
+fn add(a: Expr, b: Expr) -> Expr {
+    if let (Some((p, q)), Some((r, s))) = (fraction(&a), fraction(&b)) {
+        return divide(number(p * s + r * q), number(q * s));
+    }
+    match (literal(&a), literal(&b)) {
+        (Some(0.0), _) => b,
+        (_, Some(0.0)) => a,
+        _ if a == b => multiply(number(2.0), a),
+        _ => match (a, b) {
+            (a, Expr::Unary(UnaryOp::Negate, b)) => subtract(a, *b),
+            (Expr::Unary(UnaryOp::Negate, a), b) => subtract(b, *a),
+            (a, b) => binary(BinaryOp::Add, a, b),
+        },
+    }
+}
+
+fn subtract(a: Expr, b: Expr) -> Expr {
+    if let (Some((p, q)), Some((r, s))) = (fraction(&a), fraction(&b)) {
+        return divide(number(p * s - r * q), number(q * s));
+    }
+    match (literal(&a), literal(&b)) {
+        (Some(0.0), _) => neg(b),
+        (_, Some(0.0)) => a,
+        _ if a == b => number(0.0),
+        _ => match (a, b) {
+            (a, Expr::Unary(UnaryOp::Negate, b)) => add(a, *b),
+            // (a + b) - a = b and (a + b) - b = a
+            (Expr::Binary(BinaryOp::Add, x, y), b) if *x == b => *y,
+            (Expr::Binary(BinaryOp::Add, x, y), b) if *y == b => *x,
+            (a, b) => binary(BinaryOp::Subtract, a, b),
+        },
+    }
+}
+
+fn multiply(a: Expr, b: Expr) -> Expr {
+    match (literal(&a), literal(&b)) {
+        (Some(x), Some(y)) => return number(x * y),
+        (Some(0.0), _) | (_, Some(0.0)) => return number(0.0),
+        (Some(1.0), _) => return b,
+        (_, Some(1.0)) => return a,
+        (Some(-1.0), _) => return neg(b),
+        (_, Some(-1.0)) => return neg(a),
+        // Constant factors go first: `2 * x`, not `x * 2`.
+        (None, Some(_)) => return multiply(b, a),
+        _ => {}
+    }
+    if a == b {
+        return power(a, number(2.0));
+    }
+    match (a, b) {
+        (a, Expr::Unary(UnaryOp::Negate, b)) if literal(&b).is_none() => neg(multiply(a, *b)),
+        (Expr::Unary(UnaryOp::Negate, a), b) if literal(&a).is_none() => neg(multiply(*a, b)),
+        // Other constant factors such as `pi / 180` go first too.
+        (a, b) if is_constant(&b) && !is_constant(&a) => multiply(b, a),
+        (a, Expr::Binary(BinaryOp::Multiply, b, c)) if is_constant(&b) && !is_constant(&a) => {
+            multiply(*b, multiply(a, *c))
+        }
+        // 2 * (x / y) = 2 * x / y
+        (a, Expr::Binary(BinaryOp::Divide, b, c)) if is_constant(&a) => divide(multiply(a, *b), *c),
+        // (1 / a) * b = b / a
+        (Expr::Binary(BinaryOp::Divide, one, a), b) if literal(&one) == Some(1.0) => divide(b, *a),
+        (a, Expr::Binary(BinaryOp::Divide, one, b)) if literal(&one) == Some(1.0) => divide(a, *b),
+        // a * (b * c) = (a * b) * c, so constant factors can meet and fold.
+        (a, Expr::Binary(BinaryOp::Multiply, b, c)) => multiply(multiply(a, *b), *c),
+        (a, b) => binary(BinaryOp::Multiply, a, b),
+    }
+}
+
+fn divide(a: Expr, b: Expr) -> Expr {
+    match (literal(&a), literal(&b)) {
+        // Only fold exact quotients, so `1 / 3` stays readable.
+        (Some(x), Some(y)) if y != 0.0 && (x / y).fract() == 0.0 => return number(x / y),
+        (Some(0.0), _) => return number(0.0),
+        (_, Some(1.0)) => return a,
+        _ => {}
+    }
+    if a == b {
+        return number(1.0);
+    }
+    // Cancel numeric coefficients that divide evenly: 2 * x / (4 * y) = x / (2 * y).
+    let (n, p) = coefficient(&a);
+    let (m, q) = coefficient(&b);
+    if m.abs() != 1.0 && (n / m).fract() == 0.0 {
+        return multiply(number(n / m), divide(p, q));
+    }
+    if n.abs() != 1.0 && (m / n).fract() == 0.0 {
+        return divide(p, multiply(number(m / n), q));
+    }
+    match (a, b) {
+        (a, Expr::Unary(UnaryOp::Negate, b)) => divide(neg(a), *b),
+        (a, b) => binary(BinaryOp::Divide, a, b),
+    }
+}
+
+/// Splits a numeric coefficient off a product: `2 * x` is `(2, x)` and `x` is `(1, x)`.
+fn coefficient(expr: &Expr) -> (f64, Expr) {
+    match (literal(expr), expr) {
+        (Some(value), _) => (value, number(1.0)),
+        (None, Expr::Binary(BinaryOp::Multiply, a, b)) => match literal(a) {
+            Some(value) => (value, b.as_ref().clone()),
+            None => (1.0, expr.clone()),
+        },
+        (None, _) => (1.0, expr.clone()),
+    }
+}
+
+fn power(a: Expr, b: Expr) -> Expr {
+    match (literal(&a), literal(&b)) {
+        (Some(x), Some(y)) if y.fract() == 0.0 && (x.powf(y)).fract() == 0.0 => number(x.powf(y)),
+        (_, Some(0.0)) => number(1.0),
+        (_, Some(1.0)) => a,
+        (Some(1.0), _) => number(1.0),
+        _ => match (a, literal(&b)) {
+            // sqrt(a) ^ 2 = a, wherever the square root is real
+            (Expr::Call(name, args), Some(n)) if name == "sqrt" && n == 2.0 && args.len() == 1 => {
+                args.into_iter().next().unwrap_or_else(|| number(0.0))
+            }
+            // (a ^ m) ^ n = a ^ (m * n) for integer n; (x^2)^0.5 is |x|, not x.
+            (Expr::Binary(BinaryOp::Power, a, m), Some(n))
+                if literal(&m).is_some() && n.fract() == 0.0 =>
+            {
+                power(*a, number(literal(&m).unwrap_or(1.0) * n))
+            }
+            (a, _) => binary(BinaryOp::Power, a, b),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Statement;
+    use crate::parser::parse;
+
+    fn expr(text: &str) -> Expr {
+        match parse(text) {
+            Ok(Statement::Expr(expr)) => expr,
+            other => panic!("'{}' is not an expression: {:?}", text, other),
+        }
+    }
+
+    fn simplified(text: &str) -> String {
+        simplify(&expr(text)).to_string()
+    }
+
+    fn derivative(text: &str, calc: &Calculator) -> String {
+        differentiate(&[expr(text), Expr::Ident("x".to_string())], calc)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn folds_constants() {
+        assert_eq!(simplified("2 + 3 * 4"), "14");
+        assert_eq!(simplified("1/2 + 1/3"), "5 / 6");
+        assert_eq!(simplified("6 / 3"), "2");
+        assert_eq!(simplified("2^10"), "1024");
+    }
+
+    #[test]
+    fn removes_identities() {
+        assert_eq!(simplified("0 + x * 1"), "x");
+        assert_eq!(simplified("x - 0"), "x");
+        assert_eq!(simplified("0 * sin(x)"), "0");
+        assert_eq!(simplified("--x"), "x");
+        assert_eq!(simplified("x - x"), "0");
+        assert_eq!(simplified("x / x"), "1");
+        assert_eq!(simplified("x^1"), "x");
+        assert_eq!(simplified("x^0"), "1");
+        assert_eq!(simplified("ln(e)"), "1");
+    }
+
+    #[test]
+    fn collects_factors() {
+        assert_eq!(simplified("x * 2"), "2 * x");
+        assert_eq!(simplified("x * x"), "x ^ 2");
+        assert_eq!(simplified("x + x"), "2 * x");
+        assert_eq!(simplified("2 * x / (4 * y)"), "x / (2 * y)");
+        assert_eq!(simplified("sqrt(x)^2"), "x");
+    }
+
+    #[test]
+    fn merges_nested_powers_only_for_integer_exponents() {
+        assert_eq!(simplified("(x^2)^3"), "x ^ 6");
+        assert_eq!(simplified("(x^0.5)^2"), "x");
+        assert_eq!(simplified("(x^2)^0.5"), "(x ^ 2) ^ 0.5");
+    }
+
+    #[test]
+    fn differentiates_elementary_functions() {
+        let calc = Calculator::new();
+        assert_eq!(derivative("x^3", &calc), "3 * x ^ 2");
+        assert_eq!(derivative("5", &calc), "0");
+        assert_eq!(derivative("ln(x)", &calc), "1 / x");
+        assert_eq!(derivative("e^(2 * x)", &calc), "2 * e ^ (2 * x)");
+        assert_eq!(
+            derivative("x * sin(x)", &calc),
+            "sin(x) + pi / 180 * x * cos(x)"
+        );
+    }
+
+    #[test]
+    fn differentiates_trig_functions_in_the_angle_mode() {
+        let mut calc = Calculator::new();
+        calc.set_mode("rad").unwrap();
+        assert_eq!(derivative("sin(x)", &calc), "cos(x)");
+        calc.set_mode("deg").unwrap();
+        assert_eq!(derivative("sin(x)", &calc), "pi / 180 * cos(x)");
+    }
+}
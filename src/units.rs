@@ -0,0 +1,452 @@
+use crate::ast::{BinaryOp, Expr};
+use crate::value::{format_real, Value};
+use std::fmt;
+
+/// Exponents of the SI base dimensions, in the order of `BASE_UNITS`.
+pub type Dimension = [i8; 7];
+
+pub const DIMENSIONLESS: Dimension = [0; 7];
+
+/// Symbols of the SI base units; quantities are stored as multiples of these.
+const BASE_UNITS: [&str; 7] = ["m", "kg", "s", "A", "K", "mol", "cd"];
+
+pub const fn dim(length: i8, mass: i8, time: i8, current: i8, temperature: i8) -> Dimension {
+    [length, mass, time, current, temperature, 0, 0]
+}
+
+struct Unit {
+    symbol: &'static str,
+    /// Size of one of this unit in SI base units.
+    scale: f64,
+    dimension: Dimension,
+    /// Whether SI prefixes apply, e.g. `km`, `mA`, `kWh`.
+    prefixable: bool,
+}
+
+const fn unit(symbol: &'static str, scale: f64, dimension: Dimension, prefixable: bool) -> Unit {
+    Unit {
+        symbol,
+        scale,
+        dimension,
+        prefixable,
+    }
+}
+
+const UNITS: &[Unit] = &[
+    // SI base units (mass is prefixed from the gram).
+    unit("m", 1.0, dim(1, 0, 0, 0, 0), true),
+    unit("g", 1e-3, dim(0, 1, 0, 0, 0), true),
+    unit("s", 1.0, dim(0, 0, 1, 0, 0), true),
+    unit("A", 1.0, dim(0, 0, 0, 1, 0), true),
+    unit("K", 1.0, dim(0, 0, 0, 0, 1), true),
+    unit("mol", 1.0, [0, 0, 0, 0, 0, 1, 0], true),
+    unit("cd", 1.0, [0, 0, 0, 0, 0, 0, 1], true),
+    // Named SI derived units.
+    unit("Hz", 1.0, dim(0, 0, -1, 0, 0), true),
+    unit("N", 1.0, dim(1, 1, -2, 0, 0), true),
+    unit("Pa", 1.0, dim(-1, 1, -2, 0, 0), true),
+    unit("J", 1.0, dim(2, 1, -2, 0, 0), true),
+    unit("W", 1.0, dim(2, 1, -3, 0, 0), true),
+    unit("C", 1.0, dim(0, 0, 1, 1, 0), true),
+    unit("V", 1.0, dim(2, 1, -3, -1, 0), true),
+    unit("Ω", 1.0, dim(2, 1, -3, -2, 0), true),
+    unit("ohm", 1.0, dim(2, 1, -3, -2, 0), true),
+    unit("F", 1.0, dim(-2, -1, 4, 2, 0), true),
+    unit("T", 1.0, dim(0, 1, -2, -1, 0), true),
+    unit("H", 1.0, dim(2, 1, -2, -2, 0), true),
+    // Other metric units.
+    unit("L", 1e-3, dim(3, 0, 0, 0, 0), true),
+    unit("bar", 1e5, dim(-1, 1, -2, 0, 0), true),
+    unit("eV", 1.602176634e-19, dim(2, 1, -2, 0, 0), true),
+    unit("Wh", 3600.0, dim(2, 1, -2, 0, 0), true),
+    unit("cal", 4.184, dim(2, 1, -2, 0, 0), true),
+    unit("t", 1000.0, dim(0, 1, 0, 0, 0), false),
+    // Time.
+    unit("min", 60.0, dim(0, 0, 1, 0, 0), false),
+    unit("hr", 3600.0, dim(0, 0, 1, 0, 0), false),
+    unit("day", 86400.0, dim(0, 0, 1, 0, 0), false),
+    unit("week", 604800.0, dim(0, 0, 1, 0, 0), false),
+    unit("yr", 31557600.0, dim(0, 0, 1, 0, 0), false),
+    // Imperial, US customary and astronomical units.
+    unit("in", 0.0254, dim(1, 0, 0, 0, 0), false),
+    unit("ft", 0.3048, dim(1, 0, 0, 0, 0), false),
+    unit("yd", 0.9144, dim(1, 0, 0, 0, 0), false),
+    unit("mi", 1609.344, dim(1, 0, 0, 0, 0), false),
+    unit("nmi", 1852.0, dim(1, 0, 0, 0, 0), false),
+    unit("au", 149597870700.0, dim(1, 0, 0, 0, 0), false),
+    unit("ly", 9460730472580800.0, dim(1, 0, 0, 0, 0), false),
+    unit("gal", 3.785411784e-3, dim(3, 0, 0, 0, 0), false),
+    unit("lb", 0.45359237, dim(0, 1, 0, 0, 0), false),
+    unit("oz", 0.028349523125, dim(0, 1, 0, 0, 0), false),
+    unit("mph", 0.44704, dim(1, 0, -1, 0, 0), false),
+    unit("kn", 1852.0 / 3600.0, dim(1, 0, -1, 0, 0), false),
+    unit("atm", 101325.0, dim(-1, 1, -2, 0, 0), false),
+    unit("psi", 6894.757293168361, dim(-1, 1, -2, 0, 0), false),
+    unit("hp", 745.6998715822702, dim(2, 1, -3, 0, 0), false),
+];
+
+/// Named units used to display results that have no unit chosen with `to`.
+const DISPLAY_UNITS: &[&str] = &["Hz", "N", "Pa", "J", "W", "C", "V", "Ω", "F", "T", "H"];
+
+const PREFIXES: &[(&str, f64)] = &[
+    ("da", 1e1),
+    ("Q", 1e30),
+    ("R", 1e27),
+    ("Y", 1e24),
+    ("Z", 1e21),
+    ("E", 1e18),
+    ("P", 1e15),
+    ("T", 1e12),
+    ("G", 1e9),
+    ("M", 1e6),
+    ("k", 1e3),
+    ("h", 1e2),
+    ("d", 1e-1),
+    ("c", 1e-2),
+    ("m", 1e-3),
+    ("µ", 1e-6),
+    ("u", 1e-6),
+    ("n", 1e-9),
+    ("p", 1e-12),
+    ("f", 1e-15),
+    ("a", 1e-18),
+    ("z", 1e-21),
+    ("y", 1e-24),
+    ("r", 1e-27),
+    ("q", 1e-30),
+];
+
+/// A number with a physical dimension. Dimensionless results are plain numbers instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantity {
+    /// Magnitude in SI base units.
+    pub value: f64,
+    pub dimension: Dimension,
+    /// Unit picked with `to`, as its label and its size in SI base units.
+    pub unit: Option<(String, f64)>,
+}
+
+impl Quantity {
+    fn scalar(value: f64) -> Self {
+        Quantity {
+            value,
+            dimension: DIMENSIONLESS,
+            unit: None,
+        }
+    }
+
+    /// Plain numbers for dimensionless results, quantities otherwise.
+    fn into_value(self) -> Value {
+        if self.dimension == DIMENSIONLESS {
+            Value::Real(self.value)
+        } else {
+            Value::Quantity(self)
+        }
+    }
+}
+
+/// Looks up a unit symbol such as `m`, `km/hr`'s `km` or `mph`. Exact symbols win over
+/// prefixed ones, so `min` is minutes rather than milli-inches.
+pub fn lookup(name: &str) -> Option<Quantity> {
+    let found = UNITS
+        .iter()
+        .find(|unit| unit.symbol == name)
+        .map(|unit| (unit, 1.0))
+        .or_else(|| {
+            PREFIXES.iter().find_map(|&(prefix, factor)| {
+                let rest = name.strip_prefix(prefix)?;
+                UNITS
+                    .iter()
+                    .find(|unit| unit.prefixable && unit.symbol == rest)
+                    .map(|unit| (unit, factor))
+            })
+        });
+    found.map(|(unit, factor)| Quantity {
+        value: unit.scale * factor,
+        dimension: unit.dimension,
+        unit: None,
+    })
+}
+
+/// Converts an operand of an expression involving units; complex numbers cannot carry units.
+fn operand(value: Value) -> Result<Quantity, String> {
+    match value {
+        Value::Quantity(q) => Ok(q),
+        Value::Complex(z) if z.im != 0.0 => {
+            Err("Units cannot be combined with complex numbers".to_string())
+        }
+        other => Ok(Quantity::scalar(other.to_f64())),
+    }
+}
+
+pub fn apply_binary(op: BinaryOp, a: Value, b: Value) -> Result<Value, String> {
+    let (a, b) = (operand(a)?, operand(b)?);
+    let combine = |sign: i8| {
+        let mut dimension = a.dimension;
+        for (d, e) in dimension.iter_mut().zip(b.dimension) {
+            *d = d
+                .checked_add(sign * e)
+                .ok_or("Unit exponent out of range")?;
+        }
+        Ok::<_, String>(dimension)
+    };
+    // A unit chosen with `to` survives scaling and adding like quantities.
+    let unit = if b.dimension == DIMENSIONLESS || op == BinaryOp::Add || op == BinaryOp::Subtract {
+        a.unit.clone().or(b.unit.clone())
+    } else if a.dimension == DIMENSIONLESS {
+        b.unit.clone()
+    } else {
+        None
+    };
+    let (value, dimension) = match op {
+        BinaryOp::Add | BinaryOp::Subtract => {
+            if a.dimension != b.dimension {
+                return Err(format!(
+                    "Dimension mismatch: cannot {} {} and {}",
+                    if op == BinaryOp::Add {
+                        "add"
+                    } else {
+                        "subtract"
+                    },
+                    describe(a.dimension),
+                    describe(b.dimension)
+                ));
+            }
+            let value = if op == BinaryOp::Add {
+                a.value + b.value
+            } else {
+                a.value - b.value
+            };
+            (value, a.dimension)
+        }
+        BinaryOp::Multiply => (a.value * b.value, combine(1)?),
+        BinaryOp::Divide => {
+            if b.value == 0.0 {
+                return Err("Division by zero!".to_string());
+            }
+            (a.value / b.value, combine(-1)?)
+        }
+        BinaryOp::Power => return power(a, b),
+    };
+    Ok(Quantity {
+        value,
+        dimension,
+        unit,
+    }
+    .into_value())
+}
+
+fn power(base: Quantity, exponent: Quantity) -> Result<Value, String> {
+    if exponent.dimension != DIMENSIONLESS {
+        return Err(format!(
+            "Exponent must be dimensionless, not {}",
+            describe(exponent.dimension)
+        ));
+    }
+    let e = exponent.value;
+    let mut dimension = base.dimension;
+    for d in dimension.iter_mut() {
+        let scaled = *d as f64 * e;
+        if scaled.fract() != 0.0 || scaled.abs() > i8::MAX as f64 {
+            return Err(format!(
+                "Cannot raise {} to the power {}",
+                describe(base.dimension),
+                e
+            ));
+        }
+        *d = scaled as i8;
+    }
+    Ok(Quantity {
+        value: base.value.powf(e),
+        dimension,
+        unit: None,
+    }
+    .into_value())
+}
+
+/// Built-ins that make sense for quantities; everything else needs a plain number.
+pub fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+    match (name, args) {
+        ("sqrt", [q]) => power(operand(q.clone())?, Quantity::scalar(0.5)),
+        ("abs", [Value::Quantity(q)]) => Ok(Value::Quantity(Quantity {
+            value: q.value.abs(),
+            ..q.clone()
+        })),
+        _ => Err(format!("'{}' requires dimensionless arguments", name)),
+    }
+}
+
+/// Evaluates the target of `to`. Only unit symbols and numbers are allowed there, so a
+/// variable can't shadow the unit of the same name.
+fn resolve(expr: &Expr) -> Result<Quantity, String> {
+    match expr {
+        Expr::Ident(name) | Expr::Unit(name) => {
+            lookup(name).ok_or_else(|| format!("Unknown unit '{}'", name))
+        }
+        Expr::Number(digits) => Ok(Quantity::scalar(digits.parse().unwrap_or(f64::NAN))),
+        Expr::Binary(op @ (BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Power), a, b) => {
+            operand(apply_binary(
+                *op,
+                Value::Quantity(resolve(a)?),
+                Value::Quantity(resolve(b)?),
+            )?)
+        }
+        _ => Err(format!("'{}' is not a unit", expr)),
+    }
+}
+
+/// Re-expresses `value` in the unit `target`, e.g. `60 mph to km/hr`.
+pub fn convert(value: Value, target: &Expr) -> Result<Value, String> {
+    let label = target.to_string().replace(' ', "");
+    let target = resolve(target)?;
+    if target.dimension == DIMENSIONLESS {
+        return Err(format!("'{}' is not a unit", label));
+    }
+    let value = operand(value)?;
+    if value.dimension != target.dimension {
+        return Err(format!(
+            "Cannot convert {} to {}: dimensions differ",
+            describe(value.dimension),
+            label
+        ));
+    }
+    Ok(Value::Quantity(Quantity {
+        unit: Some((label, target.value)),
+        ..value
+    }))
+}
+
+/// Base-unit spelling of a dimension, e.g. `kg·m/s^2`.
+fn describe(dimension: Dimension) -> String {
+    let power = |symbol: &str, exponent: i8| {
+        if exponent == 1 {
+            symbol.to_string()
+        } else {
+            format!("{}^{}", symbol, exponent)
+        }
+    };
+    // Mass first so products read like `kg·m^2`.
+    let order = [1, 0, 2, 3, 4, 5, 6];
+    let numerator: Vec<String> = order
+        .iter()
+        .filter(|&&i| dimension[i] > 0)
+        .map(|&i| power(BASE_UNITS[i], dimension[i]))
+        .collect();
+    let denominator: Vec<String> = order
+        .iter()
+        .filter(|&&i| dimension[i] < 0)
+        .map(|&i| power(BASE_UNITS[i], -dimension[i]))
+        .collect();
+    let numerator = match numerator.len() {
+        0 if denominator.is_empty() => return "dimensionless".to_string(),
+        0 => "1".to_string(),
+        _ => numerator.join("·"),
+    };
+    match denominator.len() {
+        0 => numerator,
+        1 => format!("{}/{}", numerator, denominator[0]),
+        _ => format!("{}/({})", numerator, denominator.join("·")),
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((label, scale)) = &self.unit {
+            return write!(f, "{} {}", format_real(self.value / scale), label);
+        }
+        let named = DISPLAY_UNITS
+            .iter()
+            .find(|&&symbol| lookup(symbol).is_some_and(|unit| unit.dimension == self.dimension));
+        match named {
+            Some(symbol) => write!(f, "{} {}", format_real(self.value), symbol),
+            None => write!(
+                f,
+                "{} {}",
+                format_real(self.value),
+                describe(self.dimension)
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Statement;
+    use crate::parser::parse;
+
+    fn unit(text: &str) -> Expr {
+        match parse(text) {
+            Ok(Statement::Expr(expr)) => expr,
+            other => panic!("{} is not an expression: {:?}", text, other),
+        }
+    }
+
+    fn quantity(value: f64, symbol: &str) -> Value {
+        let unit = lookup(symbol).unwrap();
+        Value::Quantity(Quantity {
+            value: value * unit.value,
+            ..unit
+        })
+    }
+
+    #[test]
+    fn resolves_prefixed_units() {
+        assert_eq!(lookup("km").unwrap().value, 1e3);
+        assert_eq!(lookup("µs").unwrap().value, 1e-6);
+        assert_eq!(lookup("dam").unwrap().value, 10.0);
+        assert_eq!(lookup("mA").unwrap().dimension, dim(0, 0, 0, 1, 0));
+        // `min` is the minute, not a milli-inch; prefixes never apply to non-metric units.
+        assert_eq!(lookup("min").unwrap().value, 60.0);
+        assert!(lookup("kmi").is_none());
+        assert!(lookup("h").is_none());
+    }
+
+    #[test]
+    fn converts_between_units_of_one_dimension() {
+        let speed = apply_binary(BinaryOp::Divide, quantity(60.0, "mi"), quantity(1.0, "hr"));
+        let speed = convert(speed.unwrap(), &unit("km/hr")).unwrap();
+        assert_eq!(speed.to_string(), "96.56063999999999 km/hr");
+        let hours = convert(quantity(2.0, "hr"), &unit("min")).unwrap();
+        assert_eq!(hours.to_string(), "120 min");
+        assert_eq!(
+            convert(quantity(1.0, "m"), &unit("s")),
+            Err("Cannot convert m to s: dimensions differ".to_string())
+        );
+        assert!(convert(Value::Real(2.0), &unit("2")).is_err());
+    }
+
+    #[test]
+    fn rejects_mismatched_dimensions() {
+        assert_eq!(
+            apply_binary(BinaryOp::Add, quantity(3.0, "m"), quantity(2.0, "s")),
+            Err("Dimension mismatch: cannot add m and s".to_string())
+        );
+        let force = apply_binary(BinaryOp::Multiply, quantity(3.0, "kg"), quantity(2.0, "m"));
+        let force = apply_binary(BinaryOp::Divide, force.unwrap(), quantity(1.0, "s"));
+        let force = apply_binary(BinaryOp::Divide, force.unwrap(), quantity(1.0, "s"));
+        assert_eq!(force.unwrap().to_string(), "6 N");
+        let ratio = apply_binary(BinaryOp::Divide, quantity(1.0, "km"), quantity(1.0, "m"));
+        assert_eq!(ratio, Ok(Value::Real(1000.0)));
+    }
+
+    #[test]
+    fn measures_temperature_in_kelvin() {
+        let temperature = quantity(300.0, "K");
+        assert_eq!(temperature.to_string(), "300 K");
+        let cold = convert(quantity(1.5, "mK"), &unit("µK")).unwrap();
+        assert_eq!(cold.to_string(), "1500 µK");
+        let boltzmann = Value::Quantity(Quantity {
+            value: 1.380649e-23,
+            dimension: dim(2, 1, -2, 0, -1),
+            unit: None,
+        });
+        let energy = apply_binary(BinaryOp::Multiply, boltzmann, temperature).unwrap();
+        let energy = convert(energy, &unit("meV")).unwrap().to_string();
+        assert!(
+            energy.starts_with("25.851") && energy.ends_with(" meV"),
+            "{}",
+            energy
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use crate::complex;
+use crate::decimal::Decimal;
+use crate::matrix::Matrix;
+use crate::units::Quantity;
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, ToPrimitive};
+use std::fmt;
+
+/// Result of evaluating an expression. Exact integers come out of factorial and the
+/// combinatorics functions and stay exact through `+ - *` and integer powers; decimals
+/// come out of `precision` mode and absorb any other number they are combined with;
+/// rationals come out of `frac` mode and stay exact until an irrational function is applied;
+/// complex numbers come out of `complex` mode and absorb any other number; quantities carry
+/// physical units through floating-point arithmetic; lists hold the arguments of statistics;
+/// matrices hold floating-point entries for linear algebra.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Real(f64),
+    Integer(BigInt),
+    Decimal(Decimal),
+    Rational(BigRational),
+    Complex(Complex64),
+    Quantity(Quantity),
+    List(Vec<Value>),
+    Matrix(Matrix),
+}
+
+impl Value {
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Value::Real(value) => *value,
+            Value::Integer(n) => n.to_f64().unwrap_or(f64::NAN),
+            Value::Decimal(d) => d.to_f64(),
+            Value::Rational(r) => r.to_f64().unwrap_or(f64::NAN),
+            Value::Complex(z) if z.im == 0.0 => z.re,
+            Value::Complex(_) => f64::NAN,
+            Value::Quantity(q) => q.value,
+            Value::List(_) | Value::Matrix(_) => f64::NAN,
+        }
+    }
+
+    pub fn to_complex(&self) -> Complex64 {
+        match self {
+            Value::Complex(z) => *z,
+            _ => Complex64::new(self.to_f64(), 0.0),
+        }
+    }
+
+    /// The exact rational this value holds; floating-point reals are not exact.
+    pub fn to_rational(&self) -> Option<BigRational> {
+        match self {
+            Value::Integer(n) => Some(BigRational::from_integer(n.clone())),
+            Value::Rational(r) => Some(r.clone()),
+            Value::Real(_)
+            | Value::Decimal(_)
+            | Value::Complex(_)
+            | Value::Quantity(_)
+            | Value::List(_)
+            | Value::Matrix(_) => None,
+        }
+    }
+
+    pub fn to_decimal(&self, digits: usize) -> Result<Decimal, String> {
+        match self {
+            Value::Real(value) => Decimal::from_f64(*value, digits),
+            Value::Integer(n) => Ok(Decimal::from_integer(n.clone(), digits)),
+            Value::Decimal(d) => Ok(d.clone()),
+            Value::Rational(r) => Decimal::from_integer(r.numer().clone(), digits)
+                .div(&Decimal::from_integer(r.denom().clone(), digits)),
+            Value::Complex(z) if z.im == 0.0 => Decimal::from_f64(z.re, digits),
+            Value::Complex(_) => {
+                Err("Complex numbers cannot be used in precision mode".to_string())
+            }
+            Value::Quantity(_) => Err("Units cannot be used in precision mode".to_string()),
+            Value::List(_) => Err("Expected a number, found a list".to_string()),
+            Value::Matrix(_) => Err("Expected a number, found a matrix".to_string()),
+        }
+    }
+
+    /// The exact integer this value holds, if it is one (including integral reals like `5`).
+    pub fn to_integer(&self) -> Option<BigInt> {
+        match self {
+            Value::Real(value) if value.is_finite() && value.fract() == 0.0 => {
+                BigInt::from_f64(*value)
+            }
+            Value::Real(_) => None,
+            Value::Integer(n) => Some(n.clone()),
+            Value::Decimal(d) => d.to_integer(),
+            Value::Rational(r) => r.is_integer().then(|| r.to_integer()),
+            Value::Complex(z) if z.im == 0.0 => Value::Real(z.re).to_integer(),
+            Value::Complex(_) | Value::Quantity(_) | Value::List(_) | Value::Matrix(_) => None,
+        }
+    }
+
+    /// Formats the value, shortening exact integers longer than `max_digits`.
+    pub fn format(&self, max_digits: Option<usize>) -> String {
+        match (self, max_digits) {
+            (Value::Integer(n), Some(max_digits)) => {
+                let digits = n.magnitude().to_string();
+                if digits.len() <= max_digits {
+                    return n.to_string();
+                }
+                let sign = if n.sign() == num_bigint::Sign::Minus {
+                    "-"
+                } else {
+                    ""
+                };
+                format!(
+                    "{}{}… ({} digits)",
+                    sign,
+                    &digits[..max_digits],
+                    digits.len()
+                )
+            }
+            _ => self.to_string(),
+        }
+    }
+}
+
+/// Formats a float like `Display` does, but in scientific notation for magnitudes below 1e-7
+/// or from 1e21 up, the same thresholds decimals use.
+pub fn format_real(value: f64) -> String {
+    let magnitude = value.abs();
+    if magnitude != 0.0 && magnitude.is_finite() && !(1e-7..1e21).contains(&magnitude) {
+        format!("{:e}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Real(value) => write!(f, "{}", format_real(*value)),
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Decimal(d) => write!(f, "{}", d),
+            Value::Rational(r) => write!(f, "{}", r),
+            Value::Complex(z) => write!(f, "{}", complex::format_rectangular(*z)),
+            Value::Quantity(q) => write!(f, "{}", q),
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Value::Matrix(m) => write!(f, "{}", m),
+        }
+    }
+}